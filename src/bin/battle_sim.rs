@@ -0,0 +1,394 @@
+//Headless battle simulator, runs a roster on a board without opening a window.
+//
+//usage: battle_sim <roster> <width> <height> [max_ticks] [seed] [--record <file>] [--turns]
+//       battle_sim --replay <file>
+//
+//With --turns the combatants act one at a time in initiative order, a tick per turn.
+//
+//Each non-empty roster line places one combatant, '#' starts a comment:
+//    <faction> <x> <y> [trait ...] [+skill ...] [=item ...]
+//    <faction> * [trait ...] [+skill ...] [=item ...]
+//    Feline 0 0 Crusader +Backstab =Mail
+//    Rat 4 3
+//    Rat * Ranger
+//A '*' instead of a tile puts the combatant on a free tile picked with the seed.
+//Items (Blade, Buckler or Mail) are worn from the start. Everyone knows BasicAttack. Skills are looked up in assets/definitions next to the
+//executable, falling back to the built-in definitions when there are none.
+//Combatants are numbered in roster order starting from 0 in the summary.
+//
+//Exit status is 0 when a single faction is left standing, 1 when the tick limit
+//(or the end of a replay) is hit first and 2 when the arguments, the roster or
+//the recording could not be read.
+extern crate auto_claw;
+extern crate failure;
+extern crate generational_arena;
+
+use std::collections::HashSet;
+use std::process;
+
+use auto_claw::game::battle::outcome::{BattleResult, VictoryCondition};
+use auto_claw::game::battle::system::board::{Board, ConstructionPart};
+use auto_claw::game::definitions::{self, Definitions};
+use auto_claw::game::entities::factions::FactionId;
+use auto_claw::game::entities::items::ItemId;
+use auto_claw::game::entities::skills::{self, SkillId};
+use auto_claw::game::entities::stats::StatId;
+use auto_claw::game::entities::traits::TraitId;
+use auto_claw::game::entities::Change;
+use auto_claw::game::replay::{Recording, Replayer};
+use auto_claw::game::rng::RngStream;
+use auto_claw::game::{Event, Events, GameLoop, GameParts};
+use auto_claw::gui::resources::Resources;
+
+const DEFAULT_MAX_TICKS: u64 = 10000;
+
+struct Combatant {
+    faction: FactionId,
+    //None when any free tile will do
+    pos: Option<(usize, usize)>,
+    traits: Vec<TraitId>,
+    skills: Vec<SkillId>,
+    equipment: Vec<ItemId>,
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    if args.len() == 3 && args[1] == "--replay" {
+        process::exit(replay(&args[2]));
+    }
+    let mut record = None;
+    if let Some(i) = args.iter().position(|arg| arg == "--record") {
+        if i + 1 >= args.len() {
+            usage(&args[0]);
+        }
+        record = Some(args.remove(i + 1));
+        args.remove(i);
+    }
+    let turns = match args.iter().position(|arg| arg == "--turns") {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    };
+    if args.len() < 4 || args.len() > 6 {
+        usage(&args[0]);
+    }
+    let width = parse_size(&args[2], "width");
+    let height = parse_size(&args[3], "height");
+    let max_ticks = match args.get(4) {
+        Some(arg) => parse_arg(arg, "max_ticks") as u64,
+        None => DEFAULT_MAX_TICKS,
+    };
+    let seed = match args.get(5) {
+        Some(arg) => parse_arg(arg, "seed") as u64,
+        None => 0,
+    };
+    let definitions = load_definitions();
+    let roster = match read_roster(&args[1], width, height, &definitions) {
+        Ok(roster) => roster,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    process::exit(simulate(
+        definitions,
+        roster,
+        width,
+        height,
+        max_ticks,
+        seed,
+        record,
+        turns,
+    ));
+}
+
+fn load_definitions() -> Definitions {
+    let res = match Resources::from_relative_exe_path("assets") {
+        Ok(res) => res,
+        Err(_) => return Definitions::builtin(),
+    };
+    match Definitions::from_res(&res) {
+        Ok(definitions) => definitions,
+        Err(definitions::Error::Resource { .. }) => Definitions::builtin(),
+        Err(e) => {
+            match failure::Fail::cause(&e) {
+                Some(cause) => eprintln!("{}: {}", e, cause),
+                None => eprintln!("{}", e),
+            }
+            process::exit(2);
+        }
+    }
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "usage: {} <roster> <width> <height> [max_ticks] [seed] [--record <file>] [--turns]",
+        program
+    );
+    eprintln!("       {} --replay <file>", program);
+    process::exit(2);
+}
+
+fn parse_arg(arg: &str, name: &str) -> usize {
+    match arg.parse() {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("{} must be a whole number, got '{}'", name, arg);
+            process::exit(2);
+        }
+    }
+}
+
+//Boards need at least one tile each way
+fn parse_size(arg: &str, name: &str) -> usize {
+    match arg.parse() {
+        Ok(v) if v > 0 => v,
+        _ => {
+            eprintln!("{} must be a positive number, got '{}'", name, arg);
+            process::exit(2);
+        }
+    }
+}
+
+fn read_roster(
+    path: &str,
+    width: usize,
+    height: usize,
+    definitions: &Definitions,
+) -> Result<Vec<Combatant>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let mut roster = Vec::new();
+    let mut taken = HashSet::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let faction = parse_faction(words.next().unwrap())
+            .ok_or_else(|| format!("{}:{}: unknown faction", path, i + 1))?;
+        let expected = || format!("{}:{}: expected a tile coordinate or '*'", path, i + 1);
+        let pos = match words.next() {
+            Some("*") => None,
+            Some(x) => {
+                let x = x.parse().map_err(|_| expected())?;
+                let y = words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .ok_or_else(expected)?;
+                Some((x, y))
+            }
+            None => return Err(expected()),
+        };
+        if let Some(pos) = pos {
+            if pos.0 >= width || pos.1 >= height {
+                return Err(format!("{}:{}: {:?} is off the board", path, i + 1, pos));
+            }
+            if !taken.insert(pos) {
+                return Err(format!("{}:{}: {:?} is already taken", path, i + 1, pos));
+            }
+        }
+        let mut traits = Vec::new();
+        let mut skills = vec![SkillId::BasicAttack];
+        let mut equipment = Vec::new();
+        for word in words {
+            if word.starts_with('=') {
+                equipment.push(
+                    parse_item(&word[1..])
+                        .ok_or_else(|| format!("{}:{}: unknown item '{}'", path, i + 1, word))?,
+                );
+            } else if word.starts_with('+') {
+                skills.push(
+                    definitions
+                        .skill_id(&word[1..])
+                        .ok_or_else(|| format!("{}:{}: unknown skill '{}'", path, i + 1, word))?,
+                );
+            } else {
+                traits.push(
+                    parse_trait(word)
+                        .ok_or_else(|| format!("{}:{}: unknown trait '{}'", path, i + 1, word))?,
+                );
+            }
+        }
+        roster.push(Combatant {
+            faction: faction,
+            pos: pos,
+            traits: traits,
+            skills: skills,
+            equipment: equipment,
+        });
+    }
+    Ok(roster)
+}
+
+fn parse_faction(name: &str) -> Option<FactionId> {
+    match name {
+        "Lawless" => Some(FactionId::Lawless),
+        "Feline" => Some(FactionId::Feline),
+        "Rat" => Some(FactionId::Rat),
+        "Duck" => Some(FactionId::Duck),
+        "Arachine" => Some(FactionId::Arachine),
+        _ => None,
+    }
+}
+
+fn parse_trait(name: &str) -> Option<TraitId> {
+    match name {
+        "Crusader" => Some(TraitId::Crusader),
+        "Mage" => Some(TraitId::Mage),
+        "Priest" => Some(TraitId::Priest),
+        "Ranger" => Some(TraitId::Ranger),
+        "Bard" => Some(TraitId::Bard),
+        "Paladin" => Some(TraitId::Paladin),
+        _ => None,
+    }
+}
+
+fn parse_item(name: &str) -> Option<ItemId> {
+    match name {
+        "Blade" => Some(ItemId::Blade),
+        "Buckler" => Some(ItemId::Buckler),
+        "Mail" => Some(ItemId::Mail),
+        _ => None,
+    }
+}
+
+fn simulate(
+    definitions: Definitions,
+    roster: Vec<Combatant>,
+    width: usize,
+    height: usize,
+    max_ticks: u64,
+    seed: u64,
+    record: Option<String>,
+    turns: bool,
+) -> i32 {
+    let mut game = GameLoop::with_seed(seed);
+    game.get_parts_mut().set_definitions(definitions);
+    let mut events = Events::new();
+
+    let mut construction = Vec::new();
+    let mut anywhere = Vec::new();
+    for combatant in roster.iter() {
+        let id = game.add_entity(&mut events, None);
+        let parts = game.get_parts_mut();
+        parts.set_faction(&id, combatant.faction);
+        for skill in combatant.skills.iter() {
+            skills::add_skill(skill, &id, parts);
+        }
+        for trt in combatant.traits.iter() {
+            events.push(1, Event::ChangeEntity(id, Change::AddTrait(*trt)));
+        }
+        for item in combatant.equipment.iter() {
+            events.push(1, Event::ChangeEntity(id, Change::Equip(*item)));
+        }
+        match combatant.pos {
+            Some(pos) => construction.push(ConstructionPart::new(pos, Some(id))),
+            None => anywhere.push(id),
+        }
+    }
+    let board = Board::new(width, height);
+    let taken: Vec<(usize, usize)> = construction.iter().map(|part| part.pos()).collect();
+    let mut rng = game.get_parts().rng(RngStream::Board, None);
+    let placed = board.random_placement(&anywhere, &taken, &mut rng);
+    if placed.len() < anywhere.len() {
+        eprintln!("could not start the battle: not enough free tiles");
+        return 2;
+    }
+    construction.extend(placed);
+    if let Err(e) = game.start_battle(&mut events, board, construction) {
+        eprintln!("could not start the battle: {}", e);
+        return 2;
+    }
+
+    game.add_victory_condition(VictoryCondition::TickLimit(max_ticks));
+    if turns {
+        game.play_in_turns(Vec::new());
+    }
+    if record.is_some() {
+        game.start_recording();
+    }
+
+    while game.get_battle_result().is_none() {
+        events = GameLoop::execute_once(&mut game, events);
+    }
+
+    if let (Some(path), Some(recording)) = (record, game.stop_recording()) {
+        if let Err(e) = recording.save(&path) {
+            eprintln!("could not write {}: {}", path, e);
+            return 2;
+        }
+    }
+
+    println!("seed: {}", seed);
+    report(game.get_parts(), game.get_battle_result().as_ref().unwrap())
+}
+
+fn replay(path: &str) -> i32 {
+    let recording = match Recording::load(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("could not read {}: {}", path, e);
+            return 2;
+        }
+    };
+    let mut replayer = Replayer::new(recording);
+    let mut parts = replayer.start();
+    parts.set_definitions(load_definitions());
+    let battle = match parts.get_battle() {
+        Some(battle) => battle.clone(),
+        None => {
+            eprintln!("{} was not recorded during a battle", path);
+            return 2;
+        }
+    };
+
+    let mut winner = None;
+    while replayer.step(&mut parts).is_some() {
+        if let Some(decision) = parts.get_battle().as_ref().unwrap().decide(&parts) {
+            winner = decision;
+            break;
+        }
+    }
+    let result = parts
+        .get_battle()
+        .as_ref()
+        .unwrap_or(&battle)
+        .conclude(winner, &parts);
+    report(&parts, &result)
+}
+
+fn report(parts: &GameParts, result: &BattleResult) -> i32 {
+    match result.get_winner() {
+        Some(faction) => println!("winner: {:?}", faction),
+        None => println!("winner: none"),
+    }
+    println!("ticks: {}", result.get_ticks());
+    for (id, stats) in result.get_combatants() {
+        let entity = parts.get_entity(id);
+        println!(
+            "#{:<3}  {:<8}  damage {:>5}  kills {:>2}  xp {:>3}  level {:>2}  health {:>4}/{:<4}  {}",
+            id.into_raw_parts().0,
+            format!("{:?}", stats.faction()),
+            stats.get_damage_dealt(),
+            stats.get_kills(),
+            stats.get_xp(),
+            entity.get_level(),
+            entity.get_stat(&StatId::Health).val(),
+            entity.get_stat(&StatId::Health).max(),
+            match stats.get_died_at() {
+                Some(tick) => format!("died at tick {}", tick),
+                None => "survived".to_string(),
+            }
+        );
+    }
+
+    if result.get_winner().is_some() {
+        0
+    } else {
+        1
+    }
+}
@@ -1 +1,201 @@
+use std::collections::BTreeMap;
+
+use crate::game::battle::outcome::{
+    BattleResult, CombatantStats, VictoryCondition, XP_FOR_VICTORY, XP_PER_KILL,
+};
+use crate::game::battle::turns::TurnOrder;
+use crate::game::entities::factions::{FactionId, Factionable};
+use crate::game::entities::stats::{ModifyEvent, StatId};
+use crate::game::entities::{Cause, CharacterEvent, State};
+use crate::game::{Events, GameParts};
+use generational_arena::Index;
+
+pub mod outcome;
 pub mod system;
+pub mod turns;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "A battle is already being fought")]
+    AlreadyFighting,
+    #[fail(display = "There is no entity {:?}", id)]
+    UnknownEntity { id: Index },
+    #[fail(display = "Entity {:?} is placed more than once", id)]
+    PlacedTwice { id: Index },
+    #[fail(display = "Tile {:?} is off the board", pos)]
+    OffBoard { pos: (usize, usize) },
+    #[fail(display = "Tile {:?} is already taken", pos)]
+    TileTaken { pos: (usize, usize) },
+    #[fail(display = "No one is waiting for orders")]
+    NotWaiting,
+    #[fail(display = "Entity {:?} can not follow those orders", id)]
+    InvalidOrder { id: Index },
+}
+
+//Who is taking part in the current battle, what they were doing before it started
+//and how they have fared since
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Battle {
+    started_at: u64,
+    previous: BTreeMap<Index, State>,
+    stats: BTreeMap<Index, CombatantStats>,
+    conditions: Vec<VictoryCondition>,
+    //None while the battle is fought in real time
+    turns: Option<TurnOrder>,
+}
+impl Battle {
+    pub fn new(started_at: u64) -> Battle {
+        Battle {
+            started_at: started_at,
+            previous: BTreeMap::new(),
+            stats: BTreeMap::new(),
+            conditions: vec![VictoryCondition::LastStanding],
+            turns: None,
+        }
+    }
+    pub fn join(&mut self, id: Index, state: State, faction: FactionId) {
+        self.previous.insert(id, state);
+        self.stats.insert(id, CombatantStats::new(faction));
+    }
+    pub fn get_started_at(&self) -> u64 {
+        self.started_at
+    }
+    pub fn add_condition(&mut self, condition: VictoryCondition) {
+        self.conditions.push(condition);
+    }
+    pub fn is_participant(&self, id: &Index) -> bool {
+        self.previous.contains_key(id)
+    }
+    pub fn participants(&self) -> std::collections::btree_map::Keys<Index, State> {
+        self.previous.keys()
+    }
+    pub fn previous_state(&self, id: &Index) -> Option<&State> {
+        self.previous.get(id)
+    }
+    pub fn get_stats(&self, id: &Index) -> Option<&CombatantStats> {
+        self.stats.get(id)
+    }
+    pub fn get_turns(&self) -> Option<&TurnOrder> {
+        self.turns.as_ref()
+    }
+    pub fn get_turns_mut(&mut self) -> Option<&mut TurnOrder> {
+        self.turns.as_mut()
+    }
+    pub fn set_turns(&mut self, turns: Option<TurnOrder>) {
+        self.turns = turns;
+    }
+
+    //Tallies the damage and deaths among the character events produced while playing
+    //the given tick, deaths are counted in ticks since the battle started
+    pub fn record(&mut self, events: &Events, tick: u64) {
+        let battle_tick = tick + 1 - self.started_at;
+        for (id, char_events) in events.all_character_events() {
+            for char_event in char_events {
+                match char_event {
+                    CharacterEvent::ModifyStat(ModifyEvent::Sub(StatId::Health, amount, cause))
+                    | CharacterEvent::ModifyStat(ModifyEvent::ZeroReached(
+                        StatId::Health,
+                        amount,
+                        cause,
+                    )) => {
+                        if let Some(attacker) = cause.attacker() {
+                            if let Some(stats) = self.stats.get_mut(id) {
+                                stats.took(*amount);
+                            }
+                            if let Some(stats) = self.stats.get_mut(&attacker) {
+                                stats.dealt(*amount);
+                            }
+                        }
+                    }
+                    CharacterEvent::Death(cause) => {
+                        if let Some(stats) = self.stats.get_mut(id) {
+                            stats.died(battle_tick);
+                        }
+                        if let Cause::Agent(killer) = cause {
+                            if let Some(stats) = self.stats.get_mut(killer) {
+                                stats.killed();
+                                stats.earn(XP_PER_KILL);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    //Some(winner) once one of the victory conditions is met, a winner of None is a draw
+    pub fn decide(&self, parts: &GameParts) -> Option<Option<FactionId>> {
+        let standing: Vec<&Index> = self
+            .participants()
+            .filter(|id| match parts.get_entity(id).get_state() {
+                State::Dead(_) => false,
+                _ => true,
+            })
+            .collect();
+        for condition in self.conditions.iter() {
+            let decision = match condition {
+                VictoryCondition::LastStanding => match parts.get_board() {
+                    Some(board) => {
+                        if standing.iter().any(|id| {
+                            board
+                                .get_foes(parts.get_entity(id).faction())
+                                .iter()
+                                .any(|foe| foe != *id && standing.contains(&foe))
+                        }) {
+                            None
+                        } else {
+                            Some(standing.first().map(|id| *parts.get_entity(id).faction()))
+                        }
+                    }
+                    None => None,
+                },
+                VictoryCondition::Rout(percent) => {
+                    let mut fighters: BTreeMap<FactionId, (i32, i32)> = BTreeMap::new();
+                    for (id, stats) in self.stats.iter() {
+                        let count = fighters.entry(stats.faction()).or_insert((0, 0));
+                        count.0 += 1;
+                        if standing.contains(&id) {
+                            count.1 += 1;
+                        }
+                    }
+                    let holding: Vec<FactionId> = fighters
+                        .iter()
+                        .filter(|(_, (total, left))| {
+                            *left > 0 && (total - left) * 100 < percent * total
+                        })
+                        .map(|(faction, _)| *faction)
+                        .collect();
+                    if holding.len() <= 1 {
+                        Some(holding.first().cloned())
+                    } else {
+                        None
+                    }
+                }
+                VictoryCondition::TickLimit(ticks) => {
+                    if parts.get_tick() - self.started_at >= *ticks {
+                        Some(None)
+                    } else {
+                        None
+                    }
+                }
+            };
+            if decision.is_some() {
+                return decision;
+            }
+        }
+        None
+    }
+
+    //Adds the victory bonus to the experience earned for kills along the way and wraps the
+    //battle up into its result
+    pub fn conclude(&self, winner: Option<FactionId>, parts: &GameParts) -> BattleResult {
+        let mut combatants = self.stats.clone();
+        for stats in combatants.values_mut() {
+            if stats.get_died_at().is_none() && Some(stats.faction()) == winner {
+                stats.earn(XP_FOR_VICTORY);
+            }
+        }
+        BattleResult::new(winner, parts.get_tick() - self.started_at, combatants)
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use crate::game::entities::factions::FactionId;
+use crate::game::GameParts;
+use generational_arena::Index;
+
+pub const XP_PER_KILL: i32 = 5;
+pub const XP_FOR_VICTORY: i32 = 3;
+
+//Checked every tick in the order they were added, the first one that is met ends the battle
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum VictoryCondition {
+    //Over once nobody left standing has a foe left standing
+    LastStanding,
+    //A faction flees once it has lost at least this percentage of its fighters
+    Rout(i32),
+    //Nobody wins once the battle has gone on for this many ticks
+    TickLimit(u64),
+}
+
+//Encounter specific goals, Some(winner) once met where a winner of None is a draw
+pub trait Objective {
+    fn check(&mut self, parts: &GameParts) -> Option<Option<FactionId>>;
+}
+impl<F: FnMut(&GameParts) -> Option<Option<FactionId>>> Objective for F {
+    fn check(&mut self, parts: &GameParts) -> Option<Option<FactionId>> {
+        self(parts)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CombatantStats {
+    faction: FactionId,
+    damage_dealt: i32,
+    damage_taken: i32,
+    kills: u32,
+    died_at: Option<u64>,
+    xp: i32,
+}
+impl CombatantStats {
+    pub fn new(faction: FactionId) -> CombatantStats {
+        CombatantStats {
+            faction: faction,
+            damage_dealt: 0,
+            damage_taken: 0,
+            kills: 0,
+            died_at: None,
+            xp: 0,
+        }
+    }
+    pub fn dealt(&mut self, amount: i32) {
+        self.damage_dealt += amount;
+    }
+    pub fn took(&mut self, amount: i32) {
+        self.damage_taken += amount;
+    }
+    pub fn killed(&mut self) {
+        self.kills += 1;
+    }
+    pub fn died(&mut self, tick: u64) {
+        if self.died_at.is_none() {
+            self.died_at = Some(tick);
+        }
+    }
+    pub fn earn(&mut self, xp: i32) {
+        self.xp += xp;
+    }
+
+    pub fn faction(&self) -> FactionId {
+        self.faction
+    }
+    pub fn get_damage_dealt(&self) -> i32 {
+        self.damage_dealt
+    }
+    pub fn get_damage_taken(&self) -> i32 {
+        self.damage_taken
+    }
+    pub fn get_kills(&self) -> u32 {
+        self.kills
+    }
+    pub fn get_died_at(&self) -> Option<u64> {
+        self.died_at
+    }
+    pub fn get_xp(&self) -> i32 {
+        self.xp
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BattleResult {
+    winner: Option<FactionId>,
+    ticks: u64,
+    combatants: BTreeMap<Index, CombatantStats>,
+}
+impl BattleResult {
+    pub fn new(
+        winner: Option<FactionId>,
+        ticks: u64,
+        combatants: BTreeMap<Index, CombatantStats>,
+    ) -> BattleResult {
+        BattleResult {
+            winner: winner,
+            ticks: ticks,
+            combatants: combatants,
+        }
+    }
+    pub fn get_winner(&self) -> Option<FactionId> {
+        self.winner
+    }
+    pub fn get_ticks(&self) -> u64 {
+        self.ticks
+    }
+    pub fn get_combatants(&self) -> &BTreeMap<Index, CombatantStats> {
+        &self.combatants
+    }
+    pub fn get_casualties(&self) -> Vec<Index> {
+        self.combatants
+            .iter()
+            .filter(|(_, stats)| stats.died_at.is_some())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+    pub fn get_survivors(&self) -> Vec<Index> {
+        self.combatants
+            .iter()
+            .filter(|(_, stats)| stats.died_at.is_none())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
@@ -1,21 +1,33 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::game::battle::system::movement::{Direction, MovementPath};
 use crate::game::battle::system::CombatantRequirement;
 use crate::game::entities::factions::{FactionId, Factionable};
 use crate::game::entities::skills::Skill;
+use crate::game::entities::traits::TraitId;
 use crate::game::entities::Entity;
+use crate::game::rng::Rng;
 use crate::game::{Event, Events, GameParts, Idable};
 use generational_arena::Index;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum BoardChange {
     MoveEntity(Index, (usize, usize), (usize, usize)),
+    RemoveEntity(Index),
+    Face(Index, Direction),
+}
+
+//Where an attacker stands relative to the way its target is facing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Front,
+    Flank,
+    Behind,
 }
 
 //Current theory:
 //Each tile is a triangle, housing 1 unit, that can traverse through the tiles
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Tile {
     face_up: bool,
     x: usize,
@@ -84,7 +96,7 @@ impl Tile {
         }
     }
     pub fn up<'a>(&self, tiles: &'a Vec<Vec<Tile>>) -> Option<&'a Tile> {
-        if self.y < tiles.len() && (self.x + self.y % 2) % 2 == 0 {
+        if self.y + 1 < tiles.len() && (self.x + self.y % 2) % 2 == 0 {
             Some(&tiles[self.y + 1][self.x])
         } else {
             None
@@ -112,7 +124,7 @@ impl Tile {
         }
     }
     pub fn up_mut<'a>(&self, tiles: &'a mut Vec<Vec<Tile>>) -> Option<&'a mut Tile> {
-        if self.y < tiles.len() && (self.x + self.y % 2) % 2 == 0 {
+        if self.y + 1 < tiles.len() && (self.x + self.y % 2) % 2 == 0 {
             Some(&mut tiles[self.y + 1][self.x])
         } else {
             None
@@ -127,6 +139,18 @@ impl Tile {
     }
 }
 pub struct ConstructionPart((usize, usize), Option<Index>);
+impl ConstructionPart {
+    pub fn new(pos: (usize, usize), combatant: Option<Index>) -> ConstructionPart {
+        ConstructionPart(pos, combatant)
+    }
+    pub fn pos(&self) -> (usize, usize) {
+        self.0
+    }
+    pub fn combatant(&self) -> Option<Index> {
+        self.1
+    }
+}
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Board {
     tiles: Vec<Vec<Tile>>,
     flat: Vec<(usize, usize)>,
@@ -134,34 +158,35 @@ pub struct Board {
     width: usize,
     height: usize,
 
-    combatants: HashMap<Index, (usize, usize)>,
-    allies: HashMap<FactionId, Vec<Index>>,
-    foes: HashMap<FactionId, Vec<Index>>,
+    combatants: BTreeMap<Index, (usize, usize)>,
+    allies: BTreeMap<FactionId, Vec<Index>>,
+    foes: BTreeMap<FactionId, Vec<Index>>,
+    //The way combatants last moved or attacked in, those that have done neither look every way
+    facing: BTreeMap<Index, Direction>,
 }
 
 impl Board {
     pub fn new(width: usize, height: usize) -> Board {
         let mut tiles = Vec::new();
-        let mut face = false;
         let mut flat = Vec::new();
         for y in 0..height {
             let mut row = Vec::new();
             for x in 0..width {
-                let tile = Tile::new(face, x, y);
+                //Face up triangles share their base with the tile below, face down ones with the tile above
+                let tile = Tile::new((x + y) % 2 == 1, x, y);
                 flat.push((x, y));
                 row.push(tile);
-                face = !face;
             }
             tiles.push(row);
         }
 
-        let mut allies = HashMap::new();
+        let mut allies = BTreeMap::new();
         allies.insert(FactionId::Feline, Vec::new());
         allies.insert(FactionId::Rat, Vec::new());
         allies.insert(FactionId::Duck, Vec::new());
         allies.insert(FactionId::Arachine, Vec::new());
 
-        let mut foes = HashMap::new();
+        let mut foes = BTreeMap::new();
         foes.insert(FactionId::Lawless, Vec::new());
         foes.insert(FactionId::Feline, Vec::new());
         foes.insert(FactionId::Rat, Vec::new());
@@ -174,9 +199,10 @@ impl Board {
             height: height,
             width: width,
 
-            combatants: HashMap::new(),
+            combatants: BTreeMap::new(),
             allies: allies,
             foes: foes,
+            facing: BTreeMap::new(),
         }
     }
     pub fn add_parts(&mut self, parts: &GameParts, construction: Vec<ConstructionPart>) {
@@ -241,6 +267,30 @@ impl Board {
             }
         }
     }
+    //Free tiles picked at random for the combatants, leaving the taken ones alone. Fewer parts
+    //than combatants come back when the board runs out of room
+    pub fn random_placement(
+        &self,
+        combatants: &[Index],
+        taken: &[(usize, usize)],
+        rng: &mut Rng,
+    ) -> Vec<ConstructionPart> {
+        let mut free: Vec<(usize, usize)> = self
+            .flat
+            .iter()
+            .filter(|xy| self.get_unsafe(**xy).get().is_none() && !taken.contains(xy))
+            .cloned()
+            .collect();
+        let mut placement = Vec::new();
+        for combatant in combatants {
+            if free.is_empty() {
+                break;
+            }
+            let xy = free.swap_remove(rng.below(free.len() as u32) as usize);
+            placement.push(ConstructionPart(xy, Some(*combatant)));
+        }
+        placement
+    }
     pub fn get_unsafe(&self, (x, y): (usize, usize)) -> &Tile {
         &self.tiles[y][x]
     }
@@ -303,40 +353,155 @@ impl Board {
             BoardChange::MoveEntity(id, start, end) => {
                 self.move_entity_from_tiles(id, start.0, start.1, end.0, end.1)
             }
+            BoardChange::RemoveEntity(id) => self.remove_entity(id),
+            BoardChange::Face(id, dir) => {
+                if self.combatants.contains_key(&id) {
+                    self.facing.insert(id, dir);
+                }
+            }
         }
     }
     pub fn get_target<'a>(
         &self,
         entity: &'a Entity,
         parts: &'a GameParts,
+        rng: &mut Rng,
     ) -> Option<(&'a Entity, Skill)> {
         let id = entity.id();
         if let Some(xy) = self.combatants.get(&id) {
-            for skill in entity.get_skills() {
+            //The caster too, the skill's target rule decides whether it may aim at itself
+            let mut candidates: Vec<(&Index, &(usize, usize))> = self.combatants.iter().collect();
+            candidates.sort_by_key(|(i, _)| **i);
+            //Skills with the longest cooldowns are the strongest, they go first while ready.
+            //Priests look after their allies before anything else
+            let mut skills: Vec<&Skill> = entity.get_skills().iter().collect();
+            skills.sort_by_key(|skill| std::cmp::Reverse(skill.get_cooldown()));
+            if entity.has_trait(&TraitId::Priest) {
+                skills.sort_by_key(|skill| !skill.is_support());
+            }
+            for skill in skills {
+                if !skill.usable(entity, parts.get_tick()) {
+                    continue;
+                }
                 if skill.can_target_self() {
                     return Some((entity, *skill));
                 } else {
-                    for (i, ab) in self.combatants.iter() {
-                        if *i != id {
-                            if skill.can_target(distance(xy, ab), *i, parts) {
-                                return Some((parts.get_entity(i), *skill));
-                            }
-                        }
+                    let targets: Vec<Index> = candidates
+                        .iter()
+                        .filter(|(i, ab)| skill.can_target(&id, distance(xy, ab), **i, parts))
+                        .map(|(i, _)| **i)
+                        .collect();
+                    //Rather someone who is not already suffering what the skill inflicts,
+                    //skills that do nothing else, heals and cleanses are not worth using otherwise
+                    let fresh: Vec<Index> = targets
+                        .iter()
+                        .filter(|i| !skill.redundant_on(parts.get_entity(i)))
+                        .cloned()
+                        .collect();
+                    let targets =
+                        if fresh.is_empty() && !skill.is_effect_only() && !skill.is_support() {
+                            targets
+                        } else {
+                            fresh
+                        };
+                    //Then where the skill does the most good
+                    let scores: Vec<i32> =
+                        targets.iter().map(|i| skill.score(parts, id, *i)).collect();
+                    let best = scores.iter().max().cloned().unwrap_or(0);
+                    let targets: Vec<Index> = targets
+                        .into_iter()
+                        .zip(scores)
+                        .filter(|(_, score)| *score == best)
+                        .map(|(i, _)| i)
+                        .collect();
+                    if let Some(i) = rng.pick(&targets) {
+                        return Some((parts.get_entity(i), *skill));
                     }
                 }
             }
         }
         return None;
     }
+    pub fn get_position(&self, id: &Index) -> Option<(usize, usize)> {
+        self.combatants.get(id).cloned()
+    }
+    pub fn get_facing(&self, id: &Index) -> Option<Direction> {
+        self.facing.get(id).cloned()
+    }
+    //Combatants that have not faced anywhere yet have no back to stab
+    pub fn side_of(&self, target: &Index, attacker: &Index) -> Option<Side> {
+        let dir = Direction::towards(
+            *self.combatants.get(target)?,
+            *self.combatants.get(attacker)?,
+        )?;
+        match self.facing.get(target) {
+            Some(facing) if dir == *facing => Some(Side::Front),
+            Some(facing) if dir == facing.opposite() => Some(Side::Behind),
+            Some(_) => Some(Side::Flank),
+            None => Some(Side::Front),
+        }
+    }
+    //The triangles sharing an edge with the tile
+    pub fn adjacent(&self, xy: (usize, usize)) -> Vec<&Tile> {
+        let tile = self.get_unsafe(xy);
+        vec![
+            tile.left(&self.tiles),
+            tile.right(&self.tiles),
+            tile.up(&self.tiles),
+            tile.down(&self.tiles),
+        ]
+        .into_iter()
+        .filter_map(|tile| tile)
+        .collect()
+    }
+    pub fn distance_between(&self, a: &Index, b: &Index) -> Option<f64> {
+        Some(distance(self.combatants.get(a)?, self.combatants.get(b)?))
+    }
+    pub fn get_foes(&self, faction: &FactionId) -> &Vec<Index> {
+        self.foes.get(faction).unwrap()
+    }
+    //Lawless have no allies
+    pub fn get_allies(&self, faction: &FactionId) -> &[Index] {
+        match self.allies.get(faction) {
+            Some(allies) => allies,
+            None => &[],
+        }
+    }
+    //A step onto a free neighbouring tile that leaves the most room between the combatant
+    //and whoever it is getting away from, None when every step would close the gap
+    pub fn step_away(&self, id: &Index, from: &Index) -> Option<Direction> {
+        let xy = self.combatants.get(id)?;
+        let away = self.combatants.get(from)?;
+        let tile = self.get_unsafe(*xy);
+        let mut best = distance(xy, away);
+        let mut result = None;
+        for dir in [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ]
+        .iter()
+        {
+            if let Some(to) = tile.get_from_dir(self, *dir) {
+                let d = distance(&to.pos(), away);
+                if to.get().is_none() && d > best {
+                    best = d;
+                    result = Some(*dir);
+                }
+            }
+        }
+        result
+    }
     pub fn get_closest_ally(&self, id: Index, parts: &GameParts) -> Option<Index> {
         let faction = parts.get_entity(&id).faction();
+        //Lawless have no allies
         if let FactionId::Lawless = faction {
-        } else {
             return None;
         }
         let mut dist = 100000f64;
         let mut result: Option<Index> = None;
-        let xy = self.combatants.get(&id).unwrap();
+        let xy = self.combatants.get(&id)?;
         for ally in self.allies.get(faction).unwrap() {
             if *ally == id {
                 continue;
@@ -353,8 +518,12 @@ impl Board {
         let faction = parts.get_entity(&id).faction();
         let mut dist = 100000f64;
         let mut result: Option<Index> = None;
-        let xy = self.combatants.get(&id).unwrap();
+        let xy = self.combatants.get(&id)?;
         for ally in self.foes.get(faction).unwrap() {
+            //Lawless count themselves among their foes
+            if *ally == id {
+                continue;
+            }
             let d = distance(xy, self.combatants.get(ally).unwrap());
             if d < dist {
                 dist = d;
@@ -371,20 +540,25 @@ impl Board {
         x_end: usize,
         y_end: usize,
     ) {
-        if self
-            .tiles
-            .get_mut(x_end)
-            .unwrap()
-            .get_mut(y_end)
-            .unwrap()
-            .set_if_none(Some(id))
-        {
-            self.tiles
-                .get_mut(x_start)
-                .unwrap()
-                .get_mut(y_start)
-                .unwrap()
-                .set(None);
+        //Someone else may have moved in or out since the move was decided on
+        if self.get_unsafe((x_start, y_start)).get() != Some(id) {
+            return;
+        }
+        if self.get_mut_unsafe((x_end, y_end)).set_if_none(Some(id)) {
+            self.get_mut_unsafe((x_start, y_start)).set(None);
+            self.combatants.insert(id, (x_end, y_end));
+            if let Some(dir) = Direction::towards((x_start, y_start), (x_end, y_end)) {
+                self.facing.insert(id, dir);
+            }
+        }
+    }
+    pub fn remove_entity(&mut self, id: Index) {
+        if let Some(xy) = self.combatants.remove(&id) {
+            self.get_mut_unsafe(xy).set(None);
+        }
+        self.facing.remove(&id);
+        for members in self.allies.values_mut().chain(self.foes.values_mut()) {
+            members.retain(|member| *member != id);
         }
     }
     pub fn move_entity(
@@ -393,8 +567,8 @@ impl Board {
         path: &MovementPath,
         events: &mut Events,
     ) -> Option<MovementPath> {
-        let (x, y) = self.combatants.get(&id).unwrap();
-        let tile = self.tiles.get(*x).unwrap().get(*y).unwrap();
+        let (x, y) = self.combatants.get(&id)?;
+        let tile = self.get_unsafe((*x, *y));
         if let Some(end_tile) = match path.first() {
             Some(dir) => {
                 if let Some(to) = tile.get_from_dir(self, dir) {
@@ -423,10 +597,8 @@ impl Board {
         }
     }
     pub fn get_path_to_target(&self, start: Index, end: Index, _parts: &GameParts) -> MovementPath {
-        let (x_start, y_start) = self.combatants.get(&start).unwrap();
-        let tile_start = self.tiles.get(*x_start).unwrap().get(*y_start).unwrap();
-        let (x_end, y_end) = self.combatants.get(&end).unwrap();
-        let tile_end = self.tiles.get(*x_end).unwrap().get(*y_end).unwrap();
+        let tile_start = self.get_unsafe(*self.combatants.get(&start).unwrap());
+        let tile_end = self.get_unsafe(*self.combatants.get(&end).unwrap());
 
         if let Some(path) = self.get_path(tile_start, tile_end, MovementPath::Zero) {
             path
@@ -441,7 +613,7 @@ impl Board {
         current: MovementPath,
     ) -> Option<MovementPath> {
         if let Some(next_tile) = start.left(&self.tiles) {
-            if let Some(_) = next_tile.enhabitant {
+            if next_tile.enhabitant.is_none() || next_tile == end {
                 if let Some(last) = current.last() {
                     if last != Direction::Right {
                         self.get_path(next_tile, end, current.append(Direction::Left))
@@ -465,7 +637,7 @@ impl Board {
         current: MovementPath,
     ) -> Option<MovementPath> {
         if let Some(next_tile) = start.down(&self.tiles) {
-            if let Some(_) = next_tile.enhabitant {
+            if next_tile.enhabitant.is_none() || next_tile == end {
                 if let Some(last) = current.last() {
                     if last != Direction::Up {
                         self.get_path(next_tile, end, current.append(Direction::Down))
@@ -489,7 +661,7 @@ impl Board {
         current: MovementPath,
     ) -> Option<MovementPath> {
         if let Some(next_tile) = start.right(&self.tiles) {
-            if let Some(_) = next_tile.enhabitant {
+            if next_tile.enhabitant.is_none() || next_tile == end {
                 if let Some(last) = current.last() {
                     if last != Direction::Left {
                         self.get_path(next_tile, end, current.append(Direction::Right))
@@ -508,7 +680,7 @@ impl Board {
     }
     fn get_path_up(&self, start: &Tile, end: &Tile, current: MovementPath) -> Option<MovementPath> {
         if let Some(next_tile) = start.up(&self.tiles) {
-            if let Some(_) = next_tile.enhabitant {
+            if next_tile.enhabitant.is_none() || next_tile == end {
                 if let Some(last) = current.last() {
                     if last != Direction::Down {
                         self.get_path(next_tile, end, current.append(Direction::Up))
@@ -528,8 +700,9 @@ impl Board {
     fn get_path(&self, start: &Tile, end: &Tile, current: MovementPath) -> Option<MovementPath> {
         if start == end {
             Some(current)
-        } else if let MovementPath::Four(a, b, c, d) = current {
-            Some(MovementPath::Four(a, b, d, c))
+        } else if let MovementPath::Four(_, _, _, _) = current {
+            //Paths only look four steps ahead, they are worked out again once walked
+            Some(current)
         } else {
             match start.face_up {
                 true => {
@@ -658,5 +831,116 @@ impl Board {
 }
 
 pub fn distance((x1, y1): &(usize, usize), (x2, y2): &(usize, usize)) -> f64 {
-    (((*x2 - *x1).pow(2) + (*y2 - *y1).pow(2)) as f64).sqrt()
+    let dx = *x2 as f64 - *x1 as f64;
+    let dy = *y2 as f64 - *y1 as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entities::stats::{StatChange, StatChangeCause, StatId};
+    use crate::game::entities::Change;
+    use crate::game::rng::RngStream;
+    use crate::game::testing::settle;
+    use crate::game::GameLoop;
+
+    fn battle(roster: Vec<(FactionId, (usize, usize))>) -> (GameLoop, Vec<Index>, Events) {
+        let mut game = GameLoop::with_seed(0);
+        let mut events = Events::new();
+        let mut construction = Vec::new();
+        let mut ids = Vec::new();
+        for (faction, pos) in roster {
+            let id = game.add_entity(&mut events, None);
+            game.get_parts_mut().set_faction(&id, faction);
+            construction.push(ConstructionPart::new(pos, Some(id)));
+            ids.push(id);
+        }
+        game.start_battle(&mut events, Board::new(6, 6), construction)
+            .unwrap();
+        (game, ids, events)
+    }
+
+    #[test]
+    fn closest_ally_is_looked_up_per_faction() {
+        let (mut game, ids, events) = battle(vec![
+            (FactionId::Feline, (0, 0)),
+            (FactionId::Feline, (3, 0)),
+            (FactionId::Feline, (0, 2)),
+            (FactionId::Rat, (5, 0)),
+            (FactionId::Rat, (5, 4)),
+            (FactionId::Rat, (5, 5)),
+        ]);
+        settle(&mut game, events);
+        let parts = game.get_parts();
+        let board = parts.get_board().as_ref().unwrap();
+        assert_eq!(board.get_closest_ally(ids[0], parts), Some(ids[2]));
+        assert_eq!(board.get_closest_ally(ids[1], parts), Some(ids[0]));
+        assert_eq!(board.get_closest_ally(ids[3], parts), Some(ids[4]));
+        assert_eq!(board.get_closest_ally(ids[5], parts), Some(ids[4]));
+    }
+
+    #[test]
+    fn lawless_have_no_allies() {
+        let (mut game, ids, events) = battle(vec![
+            (FactionId::Lawless, (2, 5)),
+            (FactionId::Lawless, (3, 5)),
+            (FactionId::Feline, (0, 0)),
+            (FactionId::Feline, (1, 0)),
+        ]);
+        settle(&mut game, events);
+        let parts = game.get_parts();
+        let board = parts.get_board().as_ref().unwrap();
+        let (first, second) = (parts.get_entity(&ids[0]), parts.get_entity(&ids[1]));
+        assert!(!first.allied(second));
+        assert!(parts.get_entity(&ids[2]).allied(parts.get_entity(&ids[3])));
+        assert!(board.get_allies(&FactionId::Lawless).is_empty());
+        assert_eq!(board.get_closest_ally(ids[0], parts), None);
+        assert_eq!(board.get_closest_enemy(ids[0], parts), Some(ids[1]));
+    }
+
+    #[test]
+    fn priests_heal_the_most_injured_ally() {
+        let (mut game, ids, mut events) = battle(vec![
+            (FactionId::Feline, (2, 2)),
+            (FactionId::Feline, (3, 2)),
+            (FactionId::Feline, (2, 3)),
+            (FactionId::Rat, (5, 5)),
+        ]);
+        let prayer = game
+            .get_parts()
+            .get_definitions()
+            .skill_id("Prayer")
+            .unwrap();
+        let heal = game.get_parts().get_definitions().skill_id("Heal").unwrap();
+        events.push(
+            1,
+            Event::ChangeEntity(ids[0], Change::AddTrait(TraitId::Priest)),
+        );
+        settle(&mut game, events);
+        let mut events = Events::new();
+        //Only the single target heal is ready
+        events.push(
+            1,
+            Event::ChangeEntity(ids[0], Change::Cooldown(prayer, 1000)),
+        );
+        for (id, lost) in vec![(ids[1], -2), (ids[2], -5)] {
+            events.push(
+                1,
+                Event::ChangeStat(
+                    id,
+                    StatChange::Sub(StatId::Health, lost, StatChangeCause::PassiveRestoration),
+                ),
+            );
+        }
+        settle(&mut game, events);
+
+        let parts = game.get_parts();
+        let board = parts.get_board().as_ref().unwrap();
+        let priest = parts.get_entity(&ids[0]);
+        let mut rng = parts.rng(RngStream::Combat, Some(&ids[0]));
+        let (target, skill) = board.get_target(priest, parts, &mut rng).unwrap();
+        assert_eq!(skill.id(), heal);
+        assert_eq!(target.id(), ids[2]);
+    }
 }
@@ -5,6 +5,7 @@ use generational_arena::Index;
 
 pub mod board;
 pub mod movement;
+pub mod shapes;
 
 pub struct BoardAnalyzer {}
 impl BoardAnalyzer {
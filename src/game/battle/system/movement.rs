@@ -1,14 +1,42 @@
 use crate::game::battle::system::board::{Board, Tile};
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
     Left,
     Right,
 }
+impl Direction {
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+    //The direction that leads most directly from one tile to the other
+    pub fn towards((x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> Option<Direction> {
+        let dx = x2 as i64 - x1 as i64;
+        let dy = y2 as i64 - y1 as i64;
+        if dx == 0 && dy == 0 {
+            None
+        } else if dx.abs() >= dy.abs() {
+            if dx > 0 {
+                Some(Direction::Right)
+            } else {
+                Some(Direction::Left)
+            }
+        } else if dy > 0 {
+            Some(Direction::Up)
+        } else {
+            Some(Direction::Down)
+        }
+    }
+}
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum MovementPath {
     Zero,
     One(Direction),
@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::game::battle::system::board::Board;
+use crate::game::battle::system::movement::Direction;
+use generational_arena::Index;
+
+//Regions of the board, measured in steps across triangle edges so they follow the same
+//adjacency as movement does and stop at the edges of the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Shape {
+    //Only the target
+    Single,
+    //Everything within the given steps of the target
+    Radius(u32),
+    //Everything exactly the given steps away from the target
+    Ring(u32),
+    //Spreads out from the caster towards the target, as wide as it is far
+    Cone(u32),
+    //Straight out from the caster towards the target
+    Line(u32),
+}
+impl Default for Shape {
+    fn default() -> Shape {
+        Shape::Single
+    }
+}
+impl Shape {
+    //The tiles covered when cast from one tile at another, in the order they are reached
+    pub fn tiles(
+        &self,
+        board: &Board,
+        from: (usize, usize),
+        at: (usize, usize),
+    ) -> Vec<(usize, usize)> {
+        match *self {
+            Shape::Single => vec![at],
+            Shape::Radius(size) => reached(board, at, size)
+                .into_iter()
+                .map(|(xy, _)| xy)
+                .collect(),
+            Shape::Ring(size) => reached(board, at, size)
+                .into_iter()
+                .filter(|(_, steps)| *steps == size)
+                .map(|(xy, _)| xy)
+                .collect(),
+            Shape::Cone(size) | Shape::Line(size) => {
+                let dir = match Direction::towards(from, at) {
+                    Some(dir) => dir,
+                    None => return vec![at],
+                };
+                reached(board, from, size)
+                    .into_iter()
+                    .filter(|(xy, _)| {
+                        let (along, across) = offset(from, *xy, dir);
+                        along > 0
+                            && match self {
+                                Shape::Line(_) => across == 0,
+                                _ => across.abs() <= along,
+                            }
+                    })
+                    .map(|(xy, _)| xy)
+                    .collect()
+            }
+        }
+    }
+    //Whoever stands on the covered tiles
+    pub fn inhabitants(
+        &self,
+        board: &Board,
+        from: (usize, usize),
+        at: (usize, usize),
+    ) -> Vec<Index> {
+        self.tiles(board, from, at)
+            .into_iter()
+            .filter_map(|xy| board.get(xy).and_then(|tile| tile.get()))
+            .collect()
+    }
+}
+
+//Breadth first over shared edges, every tile within the steps and how many it took
+fn reached(board: &Board, start: (usize, usize), size: u32) -> Vec<((usize, usize), u32)> {
+    let mut steps: BTreeMap<(usize, usize), u32> = BTreeMap::new();
+    let mut order = Vec::new();
+    let mut queue = VecDeque::new();
+    if board.get(start).is_none() {
+        return order;
+    }
+    steps.insert(start, 0);
+    queue.push_back(start);
+    while let Some(xy) = queue.pop_front() {
+        let taken = steps[&xy];
+        order.push((xy, taken));
+        if taken == size {
+            continue;
+        }
+        for tile in board.adjacent(xy) {
+            if !steps.contains_key(&tile.pos()) {
+                steps.insert(tile.pos(), taken + 1);
+                queue.push_back(tile.pos());
+            }
+        }
+    }
+    order
+}
+
+//How far a tile lies in the direction from the start and how far off to the side
+fn offset(from: (usize, usize), to: (usize, usize), dir: Direction) -> (i64, i64) {
+    let dx = to.0 as i64 - from.0 as i64;
+    let dy = to.1 as i64 - from.1 as i64;
+    match dir {
+        Direction::Right => (dx, dy),
+        Direction::Left => (-dx, dy),
+        Direction::Up => (dy, dx),
+        Direction::Down => (-dy, dx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shape;
+    use crate::game::battle::system::board::Board;
+
+    const CORNER: (usize, usize) = (0, 0);
+    const MIDDLE: (usize, usize) = (3, 3);
+
+    #[test]
+    fn radius_stops_at_the_edges() {
+        let board = Board::new(6, 6);
+        assert_eq!(
+            Shape::Radius(1).tiles(&board, CORNER, CORNER),
+            vec![(0, 0), (1, 0), (0, 1)]
+        );
+        assert_eq!(
+            Shape::Radius(1).tiles(&board, MIDDLE, MIDDLE),
+            vec![(3, 3), (2, 3), (4, 3), (3, 4)]
+        );
+        assert_eq!(Shape::Radius(2).tiles(&board, CORNER, CORNER).len(), 5);
+        assert_eq!(Shape::Radius(2).tiles(&board, MIDDLE, MIDDLE).len(), 10);
+    }
+
+    #[test]
+    fn ring_leaves_out_the_inside() {
+        let board = Board::new(6, 6);
+        assert_eq!(
+            Shape::Ring(2).tiles(&board, CORNER, CORNER),
+            vec![(2, 0), (1, 1)]
+        );
+        assert_eq!(
+            Shape::Ring(2).tiles(&board, MIDDLE, MIDDLE),
+            vec![(1, 3), (2, 2), (5, 3), (4, 2), (2, 4), (4, 4)]
+        );
+    }
+
+    #[test]
+    fn cone_widens_away_from_the_caster() {
+        let board = Board::new(6, 6);
+        assert_eq!(
+            Shape::Cone(3).tiles(&board, CORNER, (3, 0)),
+            vec![(1, 0), (2, 0), (1, 1), (3, 0), (2, 1)]
+        );
+        assert_eq!(
+            Shape::Cone(2).tiles(&board, MIDDLE, (5, 3)),
+            vec![(4, 3), (5, 3), (4, 2), (4, 4)]
+        );
+    }
+
+    #[test]
+    fn line_runs_straight_to_the_edge() {
+        let board = Board::new(6, 6);
+        assert_eq!(
+            Shape::Line(3).tiles(&board, CORNER, (3, 0)),
+            vec![(1, 0), (2, 0), (3, 0)]
+        );
+        assert_eq!(
+            Shape::Line(4).tiles(&board, MIDDLE, (5, 3)),
+            vec![(4, 3), (5, 3)]
+        );
+    }
+}
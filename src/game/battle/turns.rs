@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use generational_arena::Index;
+
+//Whose turn it is when a battle is played in turns, each turn is one stance decision.
+//Player controlled combatants hold the battle up until they are given their orders.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnOrder {
+    order: Vec<Index>,
+    next: usize,
+    round: u32,
+    players: BTreeSet<Index>,
+}
+impl TurnOrder {
+    pub fn new(initiative: Vec<Index>, players: Vec<Index>) -> TurnOrder {
+        TurnOrder {
+            order: initiative,
+            next: 0,
+            round: 1,
+            players: players.into_iter().collect(),
+        }
+    }
+    pub fn current(&self) -> Option<Index> {
+        self.order.get(self.next).cloned()
+    }
+    pub fn is_player(&self, id: &Index) -> bool {
+        self.players.contains(id)
+    }
+    pub fn awaiting_input(&self) -> Option<Index> {
+        self.current().filter(|id| self.is_player(id))
+    }
+    pub fn get_order(&self) -> &Vec<Index> {
+        &self.order
+    }
+    pub fn get_round(&self) -> u32 {
+        self.round
+    }
+
+    //Passes the turn to the next combatant in the standing initiative order, once everyone
+    //has had theirs a new round starts in that order
+    pub fn advance(&mut self, standing: Vec<Index>) {
+        self.next += 1;
+        while let Some(id) = self.order.get(self.next) {
+            if standing.contains(id) {
+                return;
+            }
+            self.next += 1;
+        }
+        self.order = standing;
+        self.next = 0;
+        self.round += 1;
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use crate::game::entities::factions::{FactionDef, FactionId};
+use crate::game::entities::skills::{SkillDef, SkillId};
+use crate::game::entities::stats::StatTemplate;
+use crate::game::entities::traits::{TraitDef, TraitId};
+use crate::gui::resources::{self, Resources};
+
+pub const SKILLS_PATH: &str = "definitions/skills.ron";
+pub const TRAITS_PATH: &str = "definitions/traits.ron";
+pub const STATS_PATH: &str = "definitions/stats.ron";
+pub const FACTIONS_PATH: &str = "definitions/factions.ron";
+
+pub const DEFAULT_TEMPLATE: &str = "default";
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load definitions {}", name)]
+    Resource {
+        name: String,
+        #[cause]
+        inner: resources::Error,
+    },
+    #[fail(display = "Failed to parse definitions {}", name)]
+    Parse {
+        name: String,
+        #[cause]
+        inner: ron::de::Error,
+    },
+    #[fail(display = "Skill {} is defined more than once", name)]
+    DuplicateSkill { name: String },
+    #[fail(display = "There is no \"{}\" stat template", name)]
+    MissingTemplate { name: String },
+    #[fail(display = "Trait {:?} teaches unknown skill {}", trt, name)]
+    UnknownSkill { trt: TraitId, name: String },
+    #[fail(display = "Skill {} divides its damage by zero", name)]
+    ZeroDivisor { name: String },
+    #[fail(display = "Skill {} needs an id", name)]
+    MissingSkillId { name: String },
+    #[fail(display = "Skills {} and {} share an id", first, second)]
+    DuplicateSkillId { first: String, second: String },
+    #[fail(
+        display = "Stat template {} needs positive experience to level up",
+        name
+    )]
+    NoExperience { name: String },
+}
+
+//Everything designers tune without touching the code: skills, class traits, starting stats
+//and faction traits
+#[derive(Debug, Clone)]
+pub struct Definitions {
+    skills: BTreeMap<SkillId, SkillDef>,
+    skill_names: BTreeMap<String, SkillId>,
+    traits: BTreeMap<TraitId, TraitDef>,
+    stat_templates: BTreeMap<String, StatTemplate>,
+    factions: BTreeMap<FactionId, FactionDef>,
+}
+
+impl Definitions {
+    pub fn from_res(res: &Resources) -> Result<Definitions, Error> {
+        let load = |name: &str| {
+            res.load_string(name).map_err(|e| Error::Resource {
+                name: name.to_string(),
+                inner: e,
+            })
+        };
+        Definitions::from_str(
+            &load(SKILLS_PATH)?,
+            &load(TRAITS_PATH)?,
+            &load(STATS_PATH)?,
+            &load(FACTIONS_PATH)?,
+        )
+    }
+
+    //The definitions shipped in the assets directory, compiled in so headless runs need no files
+    pub fn builtin() -> Definitions {
+        Definitions::from_str(
+            include_str!("../../assets/definitions/skills.ron"),
+            include_str!("../../assets/definitions/traits.ron"),
+            include_str!("../../assets/definitions/stats.ron"),
+            include_str!("../../assets/definitions/factions.ron"),
+        )
+        .expect("built-in definitions are invalid")
+    }
+
+    pub fn from_str(
+        skills: &str,
+        traits: &str,
+        stats: &str,
+        factions: &str,
+    ) -> Result<Definitions, Error> {
+        let skill_defs: Vec<SkillDef> = parse(SKILLS_PATH, skills)?;
+        let traits: BTreeMap<TraitId, TraitDef> = parse(TRAITS_PATH, traits)?;
+        let stat_templates: BTreeMap<String, StatTemplate> = parse(STATS_PATH, stats)?;
+        let factions: BTreeMap<FactionId, FactionDef> = parse(FACTIONS_PATH, factions)?;
+        if !stat_templates.contains_key(DEFAULT_TEMPLATE) {
+            return Err(Error::MissingTemplate {
+                name: DEFAULT_TEMPLATE.to_string(),
+            });
+        }
+        if let Some(name) = stat_templates
+            .iter()
+            .find(|(_, template)| template.experience <= 0)
+            .map(|(name, _)| name.clone())
+        {
+            return Err(Error::NoExperience { name: name });
+        }
+
+        let mut skills: BTreeMap<SkillId, SkillDef> = BTreeMap::new();
+        let mut skill_names = BTreeMap::new();
+        for def in skill_defs {
+            if skill_names.contains_key(&def.name) {
+                return Err(Error::DuplicateSkill { name: def.name });
+            }
+            if def.damage.terms.iter().any(|term| term.div == 0) {
+                return Err(Error::ZeroDivisor { name: def.name });
+            }
+            let id = match (SkillId::builtin(&def.name), def.id) {
+                (Some(id), _) => id,
+                (None, Some(id)) => SkillId::Custom(id),
+                (None, None) => return Err(Error::MissingSkillId { name: def.name }),
+            };
+            if let Some(other) = skills.get(&id) {
+                return Err(Error::DuplicateSkillId {
+                    first: other.name.clone(),
+                    second: def.name,
+                });
+            }
+            skill_names.insert(def.name.clone(), id);
+            skills.insert(id, def);
+        }
+        for (trt, def) in traits.iter() {
+            if let Some(name) = def
+                .skills
+                .iter()
+                .find(|name| !skill_names.contains_key(*name))
+            {
+                return Err(Error::UnknownSkill {
+                    trt: *trt,
+                    name: name.clone(),
+                });
+            }
+        }
+        Ok(Definitions {
+            skills: skills,
+            skill_names: skill_names,
+            traits: traits,
+            stat_templates: stat_templates,
+            factions: factions,
+        })
+    }
+
+    pub fn skill(&self, id: &SkillId) -> Option<&SkillDef> {
+        self.skills.get(id)
+    }
+    pub fn skill_id(&self, name: &str) -> Option<SkillId> {
+        self.skill_names.get(name).cloned()
+    }
+    pub fn trait_def(&self, id: &TraitId) -> Option<&TraitDef> {
+        self.traits.get(id)
+    }
+    pub fn stat_template(&self, name: &str) -> Option<&StatTemplate> {
+        self.stat_templates.get(name)
+    }
+    pub fn faction(&self, id: &FactionId) -> Option<&FactionDef> {
+        self.factions.get(id)
+    }
+}
+
+impl Default for Definitions {
+    fn default() -> Definitions {
+        Definitions::builtin()
+    }
+}
+
+fn parse<'a, T: serde::Deserialize<'a>>(name: &str, text: &'a str) -> Result<T, Error> {
+    ron::de::from_str(text).map_err(|e| Error::Parse {
+        name: name.to_string(),
+        inner: e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_need_experience() {
+        let stats = include_str!("../../assets/definitions/stats.ron")
+            .replace("experience: 15", "experience: 0");
+        match Definitions::from_str(
+            include_str!("../../assets/definitions/skills.ron"),
+            include_str!("../../assets/definitions/traits.ron"),
+            &stats,
+            include_str!("../../assets/definitions/factions.ron"),
+        ) {
+            Err(Error::NoExperience { name }) => assert_eq!(name, DEFAULT_TEMPLATE),
+            _ => panic!("a template without experience never levels up"),
+        }
+    }
+}
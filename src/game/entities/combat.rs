@@ -2,22 +2,26 @@ use crate::game::battle::system::movement::MovementPath;
 use crate::game::entities::skills::Skill;
 use crate::game::entities::traits::TraitId;
 use crate::game::entities::{Change, Entity, Idable, State};
+use crate::game::rng::RngStream;
 use crate::game::{Event, Events, GameParts};
 use generational_arena::Index;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum CombatStance {
     FindingTarget,
     MovingCloser(Index, MovementPath),
     UsingSkill(Skill, Index),
+    //Backing off a step from a foe that got too close
+    KeepingDistance(Index),
 }
 
 impl CombatStance {
     pub fn pump<'a>(&self, entity: &Entity, parts: &GameParts, events: &mut Events) {
         if let Some(board) = parts.get_board() {
+            let mut rng = parts.rng(RngStream::Combat, Some(&entity.id()));
             match self {
                 CombatStance::FindingTarget => {
-                    if let Some(target) = board.get_target(entity, parts) {
+                    if let Some(target) = board.get_target(entity, parts, &mut rng) {
                         events.push(
                             2,
                             Event::ChangeEntity(
@@ -81,6 +85,20 @@ impl CombatStance {
                         ),
                     );
                 }
+                CombatStance::KeepingDistance(foe) => {
+                    if let Some(dir) = board.step_away(&entity.id(), foe) {
+                        board.move_entity(entity.id(), &MovementPath::One(dir), events);
+                    }
+                    events.push(
+                        2,
+                        Event::ChangeEntity(
+                            entity.id(),
+                            Change::State(State::Fighting {
+                                stance: CombatStance::FindingTarget,
+                            }),
+                        ),
+                    );
+                }
                 CombatStance::MovingCloser(target, path) => {
                     if let Some(new_path) = board.move_entity(entity.id(), path, events) {
                         events.push(
@@ -93,7 +111,7 @@ impl CombatStance {
                             ),
                         );
                     } else {
-                        if let Some(target) = board.get_target(entity, parts) {
+                        if let Some(target) = board.get_target(entity, parts, &mut rng) {
                             events.push(
                                 2,
                                 Event::ChangeEntity(
@@ -0,0 +1,299 @@
+use crate::game::entities::effects::{EffectId, INSPIRED_ACCURACY, SHIELD_ABSORBS};
+use crate::game::entities::factions::Factionable;
+use crate::game::entities::stats::{StatChange, StatChangeCause, StatId};
+use crate::game::entities::Entity;
+use crate::game::rng::Rng;
+use crate::game::{Event, Events, GameParts, Idable};
+
+//Resistances never cut more than this percentage of a hit
+pub const MAX_RESISTANCE: i32 = 75;
+
+//Percentages: the chance to land an attack before accuracy and evasion, and its bounds
+pub const BASE_HIT: i32 = 75;
+pub const MIN_HIT: i32 = 25;
+pub const MAX_HIT: i32 = 95;
+pub const BASE_CRITICAL: i32 = 5;
+pub const MAX_CRITICAL: i32 = 50;
+pub const BASE_GLANCING: i32 = 10;
+pub const MAX_GLANCING: i32 = 40;
+//Percentage of the damage dealt by critical hits and glancing blows
+pub const CRITICAL_MULTIPLIER: i32 = 150;
+pub const GLANCING_MULTIPLIER: i32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Roll {
+    Miss,
+    Glancing,
+    Hit,
+    Critical,
+}
+impl Roll {
+    pub fn landed(&self) -> bool {
+        *self != Roll::Miss
+    }
+    pub fn multiplier(&self) -> i32 {
+        match self {
+            Roll::Miss => 0,
+            Roll::Glancing => GLANCING_MULTIPLIER,
+            Roll::Hit => 100,
+            Roll::Critical => CRITICAL_MULTIPLIER,
+        }
+    }
+}
+
+//Accuracy comes from the attacker's Dexerity and Skill, evasion from the target's Dexerity.
+//Attacks that land may then turn out critical, more likely the more skilled the attacker,
+//or glancing, more likely the quicker the target is than the attacker
+pub fn roll(rng: &mut Rng, attacker: &Entity, target: &Entity) -> Roll {
+    let dex = |entity: &Entity| *entity.get_stat(&StatId::Dexerity).val();
+    let skill = *attacker.get_stat(&StatId::Skill).val();
+    let inspired = if attacker.has_effect(&EffectId::Inspired) {
+        INSPIRED_ACCURACY
+    } else {
+        0
+    };
+
+    let hit = BASE_HIT + dex(attacker) + skill + inspired - dex(target);
+    if !rng.chance(std::cmp::min(std::cmp::max(hit, MIN_HIT), MAX_HIT)) {
+        return Roll::Miss;
+    }
+    if rng.chance(std::cmp::min(
+        BASE_CRITICAL + skill * 2 + inspired,
+        MAX_CRITICAL,
+    )) {
+        return Roll::Critical;
+    }
+    let glancing = BASE_GLANCING + dex(target) - dex(attacker);
+    if rng.chance(std::cmp::min(std::cmp::max(glancing, 0), MAX_GLANCING)) {
+        Roll::Glancing
+    } else {
+        Roll::Hit
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DamageType {
+    Physical,
+    Fire,
+    Holy,
+    Poison,
+}
+impl Default for DamageType {
+    fn default() -> DamageType {
+        DamageType::Physical
+    }
+}
+
+//How a hit got from what was thrown to what was taken, every step is subtracted in order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Damage {
+    kind: DamageType,
+    raw: i32,
+    roll: Roll,
+    //Extra damage from a critical hit, negative for glancing blows
+    rolled: i32,
+    weakened: i32,
+    mitigated: i32,
+    resisted: i32,
+    absorbed: i32,
+    cause: StatChangeCause,
+}
+impl Damage {
+    pub fn get_kind(&self) -> DamageType {
+        self.kind
+    }
+    pub fn get_raw(&self) -> i32 {
+        self.raw
+    }
+    pub fn get_roll(&self) -> Roll {
+        self.roll
+    }
+    pub fn get_rolled(&self) -> i32 {
+        self.rolled
+    }
+    pub fn get_weakened(&self) -> i32 {
+        self.weakened
+    }
+    pub fn get_mitigated(&self) -> i32 {
+        self.mitigated
+    }
+    //Negative when the target is weak to the damage
+    pub fn get_resisted(&self) -> i32 {
+        self.resisted
+    }
+    pub fn get_absorbed(&self) -> i32 {
+        self.absorbed
+    }
+    pub fn get_cause(&self) -> StatChangeCause {
+        self.cause
+    }
+    pub fn total(&self) -> i32 {
+        std::cmp::max(
+            self.raw + self.rolled - self.weakened - self.mitigated - self.resisted - self.absorbed,
+            0,
+        )
+    }
+}
+
+//Every hit goes through here: the roll, the attacker's state, then the target's Fortitude
+//and equipment for physical damage, then its trait and faction resistances, then shields
+pub fn compute(
+    parts: &GameParts,
+    kind: DamageType,
+    raw: i32,
+    roll: Roll,
+    attacker: Option<&Entity>,
+    target: &Entity,
+    cause: StatChangeCause,
+) -> Damage {
+    let rolled = raw * roll.multiplier() / 100 - raw;
+    let mut left = raw + rolled;
+
+    let weakened = match attacker {
+        Some(attacker) if attacker.has_effect(&EffectId::Weakened) => left / 3,
+        _ => 0,
+    };
+    left -= weakened;
+
+    let mitigated = match kind {
+        DamageType::Physical => {
+            std::cmp::min(target.get_fortitude() / 2 + target.get_armor(), left)
+        }
+        DamageType::Fire => 0,
+        DamageType::Holy => 0,
+        DamageType::Poison => 0,
+    };
+    left -= mitigated;
+
+    let resisted = left * resistance(parts, target, kind) / 100;
+    left -= resisted;
+
+    let absorbed = if target.has_effect(&EffectId::Shielded) {
+        std::cmp::min(SHIELD_ABSORBS, std::cmp::max(left, 0))
+    } else {
+        0
+    };
+
+    Damage {
+        kind: kind,
+        raw: raw,
+        roll: roll,
+        rolled: rolled,
+        weakened: weakened,
+        mitigated: mitigated,
+        resisted: resisted,
+        absorbed: absorbed,
+        cause: cause,
+    }
+}
+
+//Percentage of the damage type the target shrugs off, negative for weaknesses
+pub fn resistance(parts: &GameParts, target: &Entity, kind: DamageType) -> i32 {
+    let defs = parts.get_definitions();
+    let from_traits: i32 = target
+        .get_traits()
+        .iter()
+        .filter_map(|trt| defs.trait_def(trt))
+        .flat_map(|def| def.resistances.iter())
+        .filter(|(resisted, _)| *resisted == kind)
+        .map(|(_, percent)| percent)
+        .sum();
+    let from_faction: i32 = match defs.faction(target.faction()) {
+        Some(def) => def
+            .resistances
+            .iter()
+            .filter(|(resisted, _)| *resisted == kind)
+            .map(|(_, percent)| percent)
+            .sum(),
+        None => 0,
+    };
+    std::cmp::min(from_traits + from_faction, MAX_RESISTANCE)
+}
+
+//Hits that were soaked up entirely leave the target alone
+pub fn deal(events: &mut Events, target: &Entity, damage: Damage) {
+    if damage.total() > 0 {
+        events.push(
+            3,
+            Event::ChangeStat(target.id(), StatChange::Damage(damage)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entities::factions::FactionId;
+    use crate::game::entities::items::ItemId;
+    use crate::game::entities::traits::TraitId;
+    use crate::game::entities::Change;
+    use crate::game::testing::settle;
+    use crate::game::GameLoop;
+    use generational_arena::Index;
+
+    //A Feline Paladin in Mail with a Buckler, Holy resistance from all three classes
+    fn armored() -> (GameLoop, Index) {
+        let mut game = GameLoop::with_seed(0);
+        let mut events = Events::new();
+        let id = game.add_entity(&mut events, None);
+        game.get_parts_mut().set_faction(&id, FactionId::Feline);
+        for change in vec![
+            Change::AddTrait(TraitId::Crusader),
+            Change::AddTrait(TraitId::Priest),
+            Change::Equip(ItemId::Mail),
+            Change::Equip(ItemId::Buckler),
+        ] {
+            events.push(1, Event::ChangeEntity(id, change));
+        }
+        settle(&mut game, events);
+        let mut events = Events::new();
+        events.push(
+            1,
+            Event::ChangeEntity(id, Change::AddTrait(TraitId::Paladin)),
+        );
+        settle(&mut game, events);
+        (game, id)
+    }
+
+    #[test]
+    fn fortitude_and_armor_mitigate_physical_hits() {
+        let (game, id) = armored();
+        let parts = game.get_parts();
+        let target = parts.get_entity(&id);
+        let damage = compute(
+            parts,
+            DamageType::Physical,
+            20,
+            Roll::Hit,
+            None,
+            target,
+            StatChangeCause::PassiveRestoration,
+        );
+        let mitigated = target.get_fortitude() / 2 + ItemId::Mail.armor() + ItemId::Buckler.armor();
+        assert_eq!(target.get_armor(), 3);
+        assert_eq!(damage.get_mitigated(), mitigated);
+        //The Feline resistance applies to what got through
+        assert_eq!(damage.get_resisted(), (20 - mitigated) * 10 / 100);
+        assert_eq!(damage.total(), 20 - mitigated - damage.get_resisted());
+    }
+
+    #[test]
+    fn resistances_are_capped() {
+        let (game, id) = armored();
+        let parts = game.get_parts();
+        let target = parts.get_entity(&id);
+        assert!(target.has_trait(&TraitId::Paladin));
+        assert_eq!(resistance(parts, target, DamageType::Holy), MAX_RESISTANCE);
+        let damage = compute(
+            parts,
+            DamageType::Holy,
+            20,
+            Roll::Hit,
+            None,
+            target,
+            StatChangeCause::PassiveRestoration,
+        );
+        assert_eq!(damage.get_mitigated(), 0);
+        assert_eq!(damage.get_resisted(), 20 * MAX_RESISTANCE / 100);
+    }
+}
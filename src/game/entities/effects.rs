@@ -0,0 +1,135 @@
+use crate::game::entities::damage::DamageType;
+use generational_arena::Index;
+
+pub const POISON_DAMAGE_PER_STACK: i32 = 1;
+pub const POISON_MAX_STACKS: u32 = 5;
+pub const BURNING_DAMAGE: i32 = 2;
+pub const SHIELD_ABSORBS: i32 = 3;
+pub const HASTE_INITIATIVE: i32 = 5;
+pub const FORTIFIED_FORTITUDE: i32 = 2;
+pub const FORTIFIED_MAX_STACKS: u32 = 3;
+pub const INSPIRED_ACCURACY: i32 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum EffectId {
+    //Damage every tick, more for every stack
+    Poison,
+    //Damage every tick
+    Burning,
+    //Can not act
+    Stun,
+    //Shorter cooldowns and earlier turns
+    Haste,
+    //Soaks up part of every hit
+    Shielded,
+    //Hits for less
+    Weakened,
+    //More Fortitude for every stack
+    Fortified,
+    //Lands and crits more often
+    Inspired,
+}
+
+pub enum Stacking {
+    //A new application only ever extends the duration
+    Refresh,
+    //Applications add up to the stack limit, each one resetting the duration
+    Intensify(u32),
+}
+
+impl EffectId {
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            EffectId::Poison => Stacking::Intensify(POISON_MAX_STACKS),
+            EffectId::Burning => Stacking::Refresh,
+            EffectId::Stun => Stacking::Refresh,
+            EffectId::Haste => Stacking::Refresh,
+            EffectId::Shielded => Stacking::Refresh,
+            EffectId::Weakened => Stacking::Refresh,
+            EffectId::Fortified => Stacking::Intensify(FORTIFIED_MAX_STACKS),
+            EffectId::Inspired => Stacking::Refresh,
+        }
+    }
+    //What a cleanse washes away
+    pub fn is_harmful(&self) -> bool {
+        match self {
+            EffectId::Poison => true,
+            EffectId::Burning => true,
+            EffectId::Stun => true,
+            EffectId::Haste => false,
+            EffectId::Shielded => false,
+            EffectId::Weakened => true,
+            EffectId::Fortified => false,
+            EffectId::Inspired => false,
+        }
+    }
+    pub fn damage_type(&self) -> DamageType {
+        match self {
+            EffectId::Poison => DamageType::Poison,
+            EffectId::Burning => DamageType::Fire,
+            EffectId::Stun => DamageType::Physical,
+            EffectId::Haste => DamageType::Physical,
+            EffectId::Shielded => DamageType::Physical,
+            EffectId::Weakened => DamageType::Physical,
+            EffectId::Fortified => DamageType::Physical,
+            EffectId::Inspired => DamageType::Physical,
+        }
+    }
+    pub fn damage_per_tick(&self, stacks: u32) -> i32 {
+        match self {
+            EffectId::Poison => POISON_DAMAGE_PER_STACK * stacks as i32,
+            EffectId::Burning => BURNING_DAMAGE,
+            EffectId::Stun => 0,
+            EffectId::Haste => 0,
+            EffectId::Shielded => 0,
+            EffectId::Weakened => 0,
+            EffectId::Fortified => 0,
+            EffectId::Inspired => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StatusEffect {
+    stacks: u32,
+    remaining: u32,
+    source: Option<Index>,
+}
+impl StatusEffect {
+    pub fn new(duration: u32, source: Option<Index>) -> StatusEffect {
+        StatusEffect {
+            stacks: 1,
+            remaining: duration,
+            source: source,
+        }
+    }
+    pub fn stack(&mut self, id: &EffectId, duration: u32, source: Option<Index>) {
+        match id.stacking() {
+            Stacking::Refresh => {
+                if duration > self.remaining {
+                    self.remaining = duration;
+                    self.source = source;
+                }
+            }
+            Stacking::Intensify(max) => {
+                self.stacks = std::cmp::min(self.stacks + 1, max);
+                self.remaining = duration;
+                self.source = source;
+            }
+        }
+    }
+    //Counts a tick off, true once the effect has run out
+    pub fn tick(&mut self) -> bool {
+        self.remaining = self.remaining.saturating_sub(1);
+        self.remaining == 0
+    }
+    pub fn get_stacks(&self) -> u32 {
+        self.stacks
+    }
+    pub fn get_remaining(&self) -> u32 {
+        self.remaining
+    }
+    pub fn get_source(&self) -> Option<Index> {
+        self.source
+    }
+}
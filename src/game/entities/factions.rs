@@ -1,4 +1,6 @@
-#[derive(Hash, PartialEq, Eq)]
+use crate::game::entities::damage::DamageType;
+
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum FactionId {
     Lawless,
 
@@ -14,7 +16,15 @@ pub trait Factionable {
     fn belongs(&self, other: &FactionId) -> bool {
         *(self.faction()) == *other
     }
+    //Lawless have no allies, not even each other
     fn allied(&self, other: &dyn Factionable) -> bool {
-        *(self.faction()) == *(other.faction())
+        *(self.faction()) != FactionId::Lawless && *(self.faction()) == *(other.faction())
     }
 }
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FactionDef {
+    //Percentage of each damage type shrugged off, negative for weaknesses
+    #[serde(default)]
+    pub resistances: Vec<(DamageType, i32)>,
+}
@@ -1,5 +1,17 @@
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum ItemId {
     Blade,
+    Buckler,
+    Mail,
+}
+impl ItemId {
+    //Flat reduction of every physical hit while the item is worn
+    pub fn armor(&self) -> i32 {
+        match self {
+            ItemId::Blade => 0,
+            ItemId::Buckler => 1,
+            ItemId::Mail => 2,
+        }
+    }
 }
 pub struct Item;
@@ -1,44 +1,115 @@
-use crate::game::{Event, Events, Idable};
-use crate::gui::animation::img::Img;
-use std::collections::hash_map::{Entry, HashMap};
-use std::collections::HashSet;
+use crate::game::battle::outcome::XP_PER_KILL;
+use crate::game::battle::system::board::BoardChange;
+use crate::game::definitions::Definitions;
+use crate::game::{Event, Events, GameParts, Idable};
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::collections::BTreeSet;
 
 use generational_arena::{Arena, Index};
 
 pub mod combat;
+pub mod damage;
+pub mod effects;
 pub mod factions;
 pub mod items;
 pub mod skills;
 pub mod stats;
 pub mod traits;
 
-#[derive(Clone, Copy)]
+//Renderer-agnostic handle to whatever draws an entity, the game itself never looks inside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VisualId(pub usize);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Change {
     AddTrait(traits::TraitId),
+    LearnSkill(skills::SkillId),
     State(State),
     SkillExp(skills::SkillId, u32),
+    //The skill can not be used again before the given tick
+    Cooldown(skills::SkillId, u64),
+    //Duration in ticks and whoever inflicted it
+    AddEffect(effects::EffectId, u32, Option<Index>),
+    TickEffects,
+    //Removes every harmful status effect
+    Cleanse,
+    Equip(items::ItemId),
+    Unequip(items::ItemId),
+    LevelUp,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Cause {
-    Agent(usize, u32),
+    Agent(Index),
     Natural(NaturalCause),
 }
-#[derive(Debug, Clone, Copy)]
-pub enum NaturalCause {}
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum NaturalCause {
+    //Worn down by something no one can be blamed for
+    Attrition,
+}
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum CharacterEvent {
     Tick,
     Birth,
     GainedTrait(traits::TraitId),
-    FailedToGainTrait(traits::TraitId),
+    FailedToGainTrait(traits::TraitId, skills::SkillRejectionReason),
     LevelUp(u32),
     LevelDown(u32),
     ModifyStat(stats::ModifyEvent),
+    //The skill and the mastery rank it reached
     SkillUp(skills::SkillId, u32),
+    FailedToCast(skills::SkillId, stats::StatId),
+    GainedEffect(effects::EffectId, u32),
+    EffectExpired(effects::EffectId),
+    //How an attack between the entity and the other one turned out, sent to both sides
+    Rolled(damage::Roll, Index),
+    //One of the entity's allies was struck
+    AllyHit(Index),
+    //The entity dealt the killing blow
+    Killed(Index),
     Death(Cause),
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CharacterEventKind {
+    Tick,
+    Birth,
+    GainedTrait,
+    FailedToGainTrait,
+    LevelUp,
+    LevelDown,
+    ModifyStat,
+    SkillUp,
+    FailedToCast,
+    GainedEffect,
+    EffectExpired,
+    Rolled,
+    AllyHit,
+    Killed,
+    Death,
+}
+impl CharacterEvent {
+    pub fn kind(&self) -> CharacterEventKind {
+        match self {
+            CharacterEvent::Tick => CharacterEventKind::Tick,
+            CharacterEvent::Birth => CharacterEventKind::Birth,
+            CharacterEvent::GainedTrait(_) => CharacterEventKind::GainedTrait,
+            CharacterEvent::FailedToGainTrait(_, _) => CharacterEventKind::FailedToGainTrait,
+            CharacterEvent::LevelUp(_) => CharacterEventKind::LevelUp,
+            CharacterEvent::LevelDown(_) => CharacterEventKind::LevelDown,
+            CharacterEvent::ModifyStat(_) => CharacterEventKind::ModifyStat,
+            CharacterEvent::SkillUp(_, _) => CharacterEventKind::SkillUp,
+            CharacterEvent::FailedToCast(_, _) => CharacterEventKind::FailedToCast,
+            CharacterEvent::GainedEffect(_, _) => CharacterEventKind::GainedEffect,
+            CharacterEvent::EffectExpired(_) => CharacterEventKind::EffectExpired,
+            CharacterEvent::Rolled(_, _) => CharacterEventKind::Rolled,
+            CharacterEvent::AllyHit(_) => CharacterEventKind::AllyHit,
+            CharacterEvent::Killed(_) => CharacterEventKind::Killed,
+            CharacterEvent::Death(_) => CharacterEventKind::Death,
+        }
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum State {
     Pupa,
     Birth,
@@ -48,87 +119,305 @@ pub enum State {
     Dead(Cause),
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Entity {
     id: Option<Index>,
     state: State,
-    img: Img,
+    visual: Option<VisualId>,
     stats: stats::StatSuite,
     faction_id: factions::FactionId,
-    traits: HashSet<traits::TraitId>,
+    traits: BTreeSet<traits::TraitId>,
     skills: Vec<skills::Skill>,
-    skill_exp: HashMap<skills::SkillId, u32>,
+    skill_exp: BTreeMap<skills::SkillId, u32>,
+    cooldowns: BTreeMap<skills::SkillId, u64>,
+    effects: BTreeMap<effects::EffectId, effects::StatusEffect>,
+    equipment: Vec<items::ItemId>,
+    level: u32,
 }
 impl Entity {
-    pub fn new(stats: stats::StatSuite, img: Img) -> Entity {
+    pub fn new(stats: stats::StatSuite, visual: Option<VisualId>) -> Entity {
         Entity {
             id: None,
             stats: stats,
-            img: img,
+            visual: visual,
             state: State::Pupa,
             faction_id: factions::FactionId::Lawless,
-            traits: HashSet::new(),
+            traits: BTreeSet::new(),
             skills: Vec::new(),
-            skill_exp: HashMap::new(),
+            skill_exp: BTreeMap::new(),
+            cooldowns: BTreeMap::new(),
+            effects: BTreeMap::new(),
+            equipment: Vec::new(),
+            level: 1,
         }
     }
     pub fn set_id(&mut self, id: Index) {
         self.id = Some(id);
     }
+    pub fn set_faction(&mut self, faction: factions::FactionId) {
+        self.faction_id = faction;
+    }
 
     pub fn has_trait(&self, trt: &traits::TraitId) -> bool {
         self.traits.contains(trt)
     }
-    pub fn gain_trait(&mut self, trt: traits::TraitId, events: &mut Events) {
-        if trt.can_gain(self) {
-            trt.on_gain(self, events);
-            self.traits.insert(trt);
-            events.character(&self.id(), CharacterEvent::GainedTrait(trt));
-        } else {
-            events.character(&self.id(), CharacterEvent::FailedToGainTrait(trt));
+    pub fn gain_trait(&mut self, trt: traits::TraitId, defs: &Definitions, events: &mut Events) {
+        match trt.can_gain(self, defs) {
+            Ok(()) => {
+                trt.on_gain(self, defs, events);
+                self.traits.insert(trt);
+                events.character(&self.id(), CharacterEvent::GainedTrait(trt));
+            }
+            Err(reason) => {
+                events.character(&self.id(), CharacterEvent::FailedToGainTrait(trt, reason))
+            }
         }
     }
     pub fn set_state<'a>(&mut self, state: State, events: &mut Events) {
+        //Nothing brings the dead back, stale changes queued before the death are dropped
+        if let State::Dead(_) = self.state {
+            return;
+        }
         self.state = state;
         match state {
             State::Birth => events.character(&self.id(), CharacterEvent::Birth),
-            State::Dead(cause) => events.character(&self.id(), CharacterEvent::Death(cause)),
+            State::Dead(cause) => {
+                events.character(&self.id(), CharacterEvent::Death(cause));
+                if let Cause::Agent(killer) = cause {
+                    events.character(&killer, CharacterEvent::Killed(self.id()));
+                }
+                events.push(1, Event::ChangeBoard(BoardChange::RemoveEntity(self.id())));
+            }
             _ => {}
         }
     }
+    //A skill that reaches a new rank is worked out again with it
     pub fn change_skill_exp(
         &mut self,
         skill_id: skills::SkillId,
         amount: u32,
+        defs: &Definitions,
+        events: &mut Events,
+    ) {
+        let before = self.get_mastery(&skill_id);
+        *self.skill_exp.entry(skill_id).or_insert(0) += amount;
+        let rank = self.get_mastery(&skill_id);
+        if rank > before {
+            if let Some(def) = defs.skill(&skill_id) {
+                let ranked = skills::Skill::from_def(skill_id, def, self);
+                if let Some(known) = self.skills.iter_mut().find(|known| known.id() == skill_id) {
+                    *known = ranked;
+                }
+            }
+            events.character(&self.id(), CharacterEvent::SkillUp(skill_id, rank));
+        }
+    }
+
+    //As many levels as the experience is enough for, none when it already went towards them
+    pub fn level_up(&mut self, defs: &Definitions, events: &mut Events) {
+        while self.stats.level_up() {
+            self.level += 1;
+            for trt in self.traits.iter() {
+                trt.on_level_up(self, defs, events);
+            }
+            events.character(&self.id(), CharacterEvent::LevelUp(self.level));
+        }
+    }
+
+    //Skills are only learned once, their numbers come from the stats at the time
+    pub fn learn_skill(&mut self, id: skills::SkillId, defs: &Definitions) {
+        if let Some(def) = defs.skill(&id) {
+            let skill = skills::Skill::from_def(id, def, self);
+            skills::Skillable::add_skill(self, skill);
+        }
+    }
+    pub fn add_effect(
+        &mut self,
+        effect: effects::EffectId,
+        duration: u32,
+        source: Option<Index>,
         events: &mut Events,
     ) {
-        let mut xp_added = false;
-        if let Entry::Occupied(mut skill) = self.skill_exp.entry(skill_id) {
-            skill.insert(skill.get() + amount);
-            xp_added = true;
+        let status = match self.effects.entry(effect) {
+            Entry::Occupied(mut status) => {
+                status.get_mut().stack(&effect, duration, source);
+                *status.get()
+            }
+            Entry::Vacant(status) => *status.insert(effects::StatusEffect::new(duration, source)),
+        };
+        events.character(
+            &self.id(),
+            CharacterEvent::GainedEffect(effect, status.get_stacks()),
+        );
+    }
+    pub fn tick_effects(&mut self, events: &mut Events) {
+        let mut expired = Vec::new();
+        for (effect, status) in self.effects.iter_mut() {
+            if status.tick() {
+                expired.push(*effect);
+            }
+        }
+        for effect in expired {
+            self.effects.remove(&effect);
+            events.character(&self.id(), CharacterEvent::EffectExpired(effect));
         }
-        if xp_added {
-            events.character(&self.id(), CharacterEvent::SkillUp(skill_id, amount));
+    }
+    pub fn cleanse(&mut self, events: &mut Events) {
+        let harmful: Vec<effects::EffectId> = self
+            .effects
+            .keys()
+            .filter(|effect| effect.is_harmful())
+            .cloned()
+            .collect();
+        for effect in harmful {
+            self.effects.remove(&effect);
+            events.character(&self.id(), CharacterEvent::EffectExpired(effect));
         }
     }
+    //Fortitude counting the Fortified stacks on top of it
+    pub fn get_fortitude(&self) -> i32 {
+        let stacks = match self.effects.get(&effects::EffectId::Fortified) {
+            Some(status) => status.get_stacks() as i32,
+            None => 0,
+        };
+        self.get_stat(&stats::StatId::Fortitude).val() + stacks * effects::FORTIFIED_FORTITUDE
+    }
+    pub fn is_injured(&self) -> bool {
+        let health = self.get_stat(&stats::StatId::Health);
+        health.val() < health.max()
+    }
+    //Health left as a percentage of the most there can be
+    pub fn health_percent(&self) -> i32 {
+        let health = self.get_stat(&stats::StatId::Health);
+        if *health.max() > 0 {
+            health.val() * 100 / health.max()
+        } else {
+            0
+        }
+    }
+    //Lingering damage happens whenever the effects are due, the durations run down afterwards
+    pub fn pump_effects(&self, parts: &GameParts, new_events: &mut Events) {
+        if self.effects.is_empty() {
+            return;
+        }
+        for (effect, status) in self.effects.iter() {
+            let damage = effect.damage_per_tick(status.get_stacks());
+            if damage > 0 {
+                damage::deal(
+                    new_events,
+                    self,
+                    damage::compute(
+                        parts,
+                        effect.damage_type(),
+                        damage,
+                        damage::Roll::Hit,
+                        None,
+                        self,
+                        stats::StatChangeCause::Effect(*effect, status.get_source()),
+                    ),
+                );
+            }
+        }
+        new_events.push(2, Event::ChangeEntity(self.id(), Change::TickEffects));
+    }
 
-    pub fn pump<'a>(&self, char_events: Vec<CharacterEvent>, new_events: &mut Events) {
+    pub fn pump<'a>(
+        &self,
+        parts: &GameParts,
+        char_events: Vec<CharacterEvent>,
+        new_events: &mut Events,
+    ) {
+        if let State::Dead(_) = self.state {
+            return;
+        }
+        let mut dying = None;
+        let mut leveled = false;
+        //A trait that changes what the entity is doing overrides its stance for the tick
+        let mut reacted = false;
         for char_event in char_events {
+            if let CharacterEvent::ModifyStat(stats::ModifyEvent::ZeroReached(
+                stats::StatId::Health,
+                _,
+                cause,
+            )) = char_event
+            {
+                //A heal landing in the same tick pulls them back from the brink
+                if *self.get_stat(&stats::StatId::Health).val() <= 0 {
+                    dying = Some(cause.blame());
+                }
+            }
+            if let CharacterEvent::ModifyStat(stats::ModifyEvent::MaxReached(
+                stats::StatId::Experience,
+                _,
+                _,
+            )) = char_event
+            {
+                if !leveled {
+                    leveled = true;
+                    new_events.push(2, Event::ChangeEntity(self.id(), Change::LevelUp));
+                }
+            }
+            if let CharacterEvent::Killed(victim) = char_event {
+                stats::StatId::Experience.event_change_for(
+                    new_events,
+                    self.id(),
+                    XP_PER_KILL,
+                    stats::StatChangeCause::Kill(victim),
+                );
+            }
+            if let (CharacterEvent::Tick, true) = (char_event, parts.effects_due(&self.id())) {
+                self.pump_effects(parts, new_events);
+            }
             for trt in self.traits.iter() {
-                match trt.handle(self, char_event) {
-                    Some(change) => new_events.push(2, Event::ChangeEntity(self.id(), change)),
-                    None => (),
+                for (id, change) in trt.handle(self, char_event, parts) {
+                    if let (true, Change::State(_)) = (id == self.id(), change) {
+                        reacted = true;
+                    }
+                    new_events.push(2, Event::ChangeEntity(id, change));
                 }
             }
         }
+        match (dying, self.state) {
+            (Some(cause), _) => new_events.push(
+                1,
+                Event::ChangeEntity(self.id(), Change::State(State::Dead(cause))),
+            ),
+            (None, State::Fighting { stance })
+                if !reacted && parts.may_act(&self.id()) && self.can_act() =>
+            {
+                stance.pump(self, parts, new_events)
+            }
+            _ => (),
+        }
     }
-    pub fn change<'a>(&mut self, change: Change, events: &mut Events) {
+    pub fn change<'a>(&mut self, change: Change, defs: &Definitions, events: &mut Events) {
         match change {
-            Change::AddTrait(trt) => self.gain_trait(trt, events),
+            Change::AddTrait(trt) => self.gain_trait(trt, defs, events),
+            Change::LearnSkill(skill) => self.learn_skill(skill, defs),
             Change::State(state) => self.set_state(state, events),
-            Change::SkillExp(skill, amount) => self.change_skill_exp(skill, amount, events),
+            Change::SkillExp(skill, amount) => self.change_skill_exp(skill, amount, defs, events),
+            Change::Cooldown(skill, ready_at) => {
+                self.cooldowns.insert(skill, ready_at);
+            }
+            Change::AddEffect(effect, duration, source) => {
+                self.add_effect(effect, duration, source, events)
+            }
+            Change::TickEffects => self.tick_effects(events),
+            Change::Cleanse => self.cleanse(events),
+            Change::Equip(item) => self.equipment.push(item),
+            Change::Unequip(item) => {
+                if let Some(i) = self.equipment.iter().position(|worn| *worn == item) {
+                    self.equipment.remove(i);
+                }
+            }
+            Change::LevelUp => self.level_up(defs, events),
         }
     }
     pub fn change_stat(&mut self, id: &Index, change: stats::StatChange, events: &mut Events) {
+        //Heals and hits still on their way when someone dies land on a corpse
+        if let State::Dead(_) = self.state {
+            return;
+        }
         self.stats.change(id, change, events);
     }
     pub fn modify_stat(
@@ -139,12 +428,58 @@ impl Entity {
     ) -> stats::ModifyEvent {
         self.stats.modify(id, v, cause)
     }
+    pub fn get_state(&self) -> &State {
+        &self.state
+    }
     pub fn get_skills(&self) -> &Vec<skills::Skill> {
         &self.skills
     }
+    pub fn get_skill_exp(&self, skill: &skills::SkillId) -> u32 {
+        self.skill_exp.get(skill).cloned().unwrap_or(0)
+    }
+    pub fn get_mastery(&self, skill: &skills::SkillId) -> u32 {
+        skills::mastery_rank(self.get_skill_exp(skill))
+    }
+    pub fn get_level(&self) -> u32 {
+        self.level
+    }
+    pub fn get_traits(&self) -> &BTreeSet<traits::TraitId> {
+        &self.traits
+    }
+    pub fn get_equipment(&self) -> &Vec<items::ItemId> {
+        &self.equipment
+    }
+    pub fn get_armor(&self) -> i32 {
+        self.equipment.iter().map(|item| item.armor()).sum()
+    }
+    pub fn has_effect(&self, effect: &effects::EffectId) -> bool {
+        self.effects.contains_key(effect)
+    }
+    pub fn get_effect(&self, effect: &effects::EffectId) -> Option<&effects::StatusEffect> {
+        self.effects.get(effect)
+    }
+    pub fn get_effects(&self) -> &BTreeMap<effects::EffectId, effects::StatusEffect> {
+        &self.effects
+    }
+    //Alive and not held back by anything
+    pub fn can_act(&self) -> bool {
+        match self.state {
+            State::Dead(_) => false,
+            _ => !self.has_effect(&effects::EffectId::Stun),
+        }
+    }
+    pub fn is_ready(&self, skill: &skills::SkillId, tick: u64) -> bool {
+        match self.cooldowns.get(skill) {
+            Some(ready_at) => tick >= *ready_at,
+            None => true,
+        }
+    }
     pub fn get_stat(&self, stat: &stats::StatId) -> &stats::Stat {
         &self.stats.get(stat)
     }
+    pub fn get_visual(&self) -> Option<VisualId> {
+        self.visual
+    }
 }
 
 //impl <'b> crate::game::Executable<'b> for Entity<'b> {
@@ -161,7 +496,9 @@ impl Idable for Entity {
 
 impl skills::Skillable for Entity {
     fn add_skill(&mut self, skill: skills::Skill) {
-        self.skills.push(skill);
+        if !self.skills.iter().any(|known| known.id() == skill.id()) {
+            self.skills.push(skill);
+        }
     }
 }
 impl traits::Traitable for Entity {
@@ -184,3 +521,90 @@ impl std::fmt::Debug for Entity {
         write!(formatter, "{:?}", "Hi")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::battle::outcome::XP_PER_KILL;
+    use crate::game::testing::settle;
+    use crate::game::GameLoop;
+
+    fn crusader() -> (GameLoop, Index) {
+        let mut game = GameLoop::with_seed(0);
+        let mut events = Events::new();
+        let id = game.add_entity(&mut events, None);
+        events.push(
+            1,
+            Event::ChangeEntity(id, Change::AddTrait(traits::TraitId::Crusader)),
+        );
+        settle(&mut game, events);
+        (game, id)
+    }
+
+    fn reward(game: &mut GameLoop, id: Index, xp: i32) {
+        let mut events = Events::new();
+        let reward = stats::StatChange::Add(
+            stats::StatId::Experience,
+            xp,
+            stats::StatChangeCause::BattleReward,
+        );
+        events.push(1, Event::ChangeStat(id, reward));
+        events.push(2, Event::ChangeEntity(id, Change::LevelUp));
+        settle(game, events);
+    }
+
+    #[test]
+    fn levels_grow_the_stats_of_every_trait() {
+        let (mut game, id) = crusader();
+        let before = game.get_parts().get_entity(&id).clone();
+        let growth = game
+            .get_parts()
+            .get_definitions()
+            .trait_def(&traits::TraitId::Crusader)
+            .unwrap()
+            .growth
+            .clone();
+        let first = *before.get_stat(&stats::StatId::Experience).max();
+        reward(&mut game, id, first);
+
+        let after = game.get_parts().get_entity(&id);
+        assert_eq!(after.get_level(), 2);
+        for (stat, bonus) in growth {
+            assert_eq!(
+                *after.get_stat(&stat).max(),
+                before.get_stat(&stat).max() + bonus
+            );
+        }
+    }
+
+    #[test]
+    fn a_big_reward_is_worth_several_levels() {
+        let (mut game, id) = crusader();
+        let first = *game
+            .get_parts()
+            .get_entity(&id)
+            .get_stat(&stats::StatId::Experience)
+            .max();
+        let second = first * stats::EXPERIENCE_CURVE / 100;
+        reward(&mut game, id, first + second + 1);
+
+        let entity = game.get_parts().get_entity(&id);
+        assert_eq!(entity.get_level(), 3);
+        assert_eq!(*entity.get_stat(&stats::StatId::Experience).val(), 1);
+    }
+
+    #[test]
+    fn kills_are_paid_for_straight_away() {
+        let (mut game, id) = crusader();
+        let victim = game.add_entity(&mut Events::new(), None);
+        let mut events = Events::new();
+        events.character(&id, CharacterEvent::Killed(victim));
+        let events = game.get_parts_mut().tick(events);
+        settle(&mut game, events);
+        let entity = game.get_parts().get_entity(&id);
+        assert_eq!(
+            *entity.get_stat(&stats::StatId::Experience).val(),
+            XP_PER_KILL
+        );
+    }
+}
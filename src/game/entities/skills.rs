@@ -1,9 +1,36 @@
-use crate::game::entities::stats::{StatChange, StatChangeCause};
-use crate::game::entities::Change;
+use crate::game::battle::system::board::{BoardChange, Side};
+use crate::game::battle::system::movement::Direction;
+use crate::game::battle::system::shapes::Shape;
+use crate::game::entities::damage::{self, DamageType};
+use crate::game::entities::effects::EffectId;
+use crate::game::entities::factions::{FactionId, Factionable};
+use crate::game::entities::stats::StatChangeCause;
+use crate::game::entities::traits::TraitId;
+use crate::game::entities::{Change, CharacterEvent, Entity};
+use crate::game::rng::{Rng, RngStream};
 use crate::game::{Event, Events, GameParts, Idable};
 use generational_arena::Index;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+//Percentage of its damage a Backstab deals from behind and from the side of its target
+pub const BACKSTAB_BEHIND: i32 = 200;
+pub const BACKSTAB_FLANK: i32 = 150;
+//Experience the caster earns each time a skill lands
+pub const XP_PER_USE: i32 = 1;
+//Uses of a skill it takes to reach each mastery rank
+pub const MASTERY_RANKS: [u32; 4] = [5, 15, 30, 50];
+//Percentages every mastery rank adds to the damage and range of a skill and takes off its costs
+pub const MASTERY_DAMAGE: i32 = 10;
+pub const MASTERY_RANGE: i32 = 10;
+pub const MASTERY_COST: i32 = 10;
+
+pub fn mastery_rank(exp: u32) -> u32 {
+    MASTERY_RANKS
+        .iter()
+        .filter(|needed| exp >= **needed)
+        .count() as u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum SkillId {
     BasicAttack,
 
@@ -12,10 +39,110 @@ pub enum SkillId {
 
     //Mage
     FireBall,
+
+    //Skills that only exist in the definition files, by the id given there
+    Custom(u16),
+}
+impl SkillId {
+    pub fn builtin(name: &str) -> Option<SkillId> {
+        match name {
+            "BasicAttack" => Some(SkillId::BasicAttack),
+            "Backstab" => Some(SkillId::Backstab),
+            "FireBall" => Some(SkillId::FireBall),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkillEffect {
+    Strike,
+    Backstab,
+    FireBall,
+    //Only inflicts its status effect
+    Inflict,
+    //Restores as much Health as the skill's damage
+    Heal,
+    //Removes harmful status effects
+    Cleanse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetRule {
+    Foe,
+    Ally,
+    //The caster or one of its allies
+    Friend,
+    Myself,
+    Anyone,
 }
 
-pub enum SkillRejectionReason<'a> {
-    StatTooLow(&'a dyn SkillRequirement, i32),
+fn one() -> i32 {
+    1
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatTerm {
+    pub stat: StatId,
+    #[serde(default = "one")]
+    pub mul: i32,
+    #[serde(default = "one")]
+    pub div: i32,
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Formula {
+    #[serde(default)]
+    pub base: i32,
+    #[serde(default)]
+    pub terms: Vec<StatTerm>,
+}
+impl Formula {
+    pub fn eval(&self, parts: &GameParts, entity: &Index) -> i32 {
+        self.eval_for(parts.get_entity(entity))
+    }
+    pub fn eval_for(&self, entity: &Entity) -> i32 {
+        self.terms.iter().fold(self.base, |acc, term| {
+            acc + entity.get_stat(&term.stat).val() * term.mul / term.div
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillDef {
+    pub name: String,
+    //Required for skills the game does not know, so saves keep finding them
+    #[serde(default)]
+    pub id: Option<u16>,
+    pub effect: SkillEffect,
+    pub target: TargetRule,
+    pub range: f64,
+    #[serde(default)]
+    pub damage: Formula,
+    #[serde(default)]
+    pub kind: DamageType,
+    #[serde(default)]
+    pub shape: Shape,
+    #[serde(default)]
+    pub stamina: i32,
+    #[serde(default)]
+    pub mana: i32,
+    #[serde(default)]
+    pub cooldown: u64,
+    //Status effect put on the target and for how many ticks
+    #[serde(default)]
+    pub inflicts: Option<(EffectId, u32)>,
+}
+
+//Why an entity could not learn a skill or gain a trait
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SkillRejectionReason {
+    //The stat and how far short of the requirement it fell
+    StatTooLow(StatId, i32),
+    MissingTrait(TraitId),
+    //The level required
+    LevelTooLow(u32),
+    //The faction required
+    WrongFaction(FactionId),
+    AlreadyKnown,
 }
 
 pub trait Skillable: Idable + std::fmt::Debug {
@@ -23,44 +150,59 @@ pub trait Skillable: Idable + std::fmt::Debug {
 }
 
 pub trait SkillRequirement {
-    fn satisfies(&self, learner: &Index, _parts: &GameParts) -> Option<SkillRejectionReason>;
+    fn satisfies(&self, learner: &Entity) -> Option<SkillRejectionReason>;
 }
 
 pub struct RequireAny;
 impl SkillRequirement for RequireAny {
-    fn satisfies(&self, _learner: &Index, _parts: &GameParts) -> Option<SkillRejectionReason> {
+    fn satisfies(&self, _learner: &Entity) -> Option<SkillRejectionReason> {
         None
     }
 }
-pub struct EachRequirement<'a> {
-    requirements: Vec<&'a dyn SkillRequirement>,
+//Satisfied when all of the requirements are, otherwise rejected for the first that is not
+pub struct EachRequirement {
+    requirements: Vec<Box<dyn SkillRequirement>>,
+}
+impl EachRequirement {
+    pub fn new(requirements: Vec<Box<dyn SkillRequirement>>) -> EachRequirement {
+        EachRequirement {
+            requirements: requirements,
+        }
+    }
 }
-impl SkillRequirement for EachRequirement<'_> {
-    fn satisfies(&self, learner: &Index, parts: &GameParts) -> Option<SkillRejectionReason> {
-        (&self.requirements)
-            .into_iter()
-            .map(|r| r.satisfies(learner, parts))
-            .fold(None, |acc, r| match acc {
-                None => r,
-                Some(reason) => Some(reason),
-            })
+impl SkillRequirement for EachRequirement {
+    fn satisfies(&self, learner: &Entity) -> Option<SkillRejectionReason> {
+        self.requirements
+            .iter()
+            .filter_map(|r| r.satisfies(learner))
+            .next()
     }
 }
-pub struct AlternativeRequirement<'a> {
-    requirements: Vec<&'a dyn SkillRequirement>,
+//Satisfied when any of the requirements is, otherwise rejected for the first of them
+pub struct AlternativeRequirement {
+    requirements: Vec<Box<dyn SkillRequirement>>,
+}
+impl AlternativeRequirement {
+    pub fn new(requirements: Vec<Box<dyn SkillRequirement>>) -> AlternativeRequirement {
+        AlternativeRequirement {
+            requirements: requirements,
+        }
+    }
 }
-impl SkillRequirement for AlternativeRequirement<'_> {
-    fn satisfies(&self, learner: &Index, parts: &GameParts) -> Option<SkillRejectionReason> {
-        (&self.requirements)
-            .into_iter()
-            .map(|r| r.satisfies(learner, parts))
-            .fold(None, |acc, r| match r {
-                None => None,
-                Some(reason) => match acc {
-                    None => Some(reason),
-                    Some(other_reason) => Some(other_reason),
-                },
-            })
+impl SkillRequirement for AlternativeRequirement {
+    fn satisfies(&self, learner: &Entity) -> Option<SkillRejectionReason> {
+        let mut first = None;
+        for requirement in self.requirements.iter() {
+            match requirement.satisfies(learner) {
+                None => return None,
+                Some(reason) => {
+                    if first.is_none() {
+                        first = Some(reason);
+                    }
+                }
+            }
+        }
+        first
     }
 }
 use crate::game::entities::stats::StatId;
@@ -68,27 +210,77 @@ pub struct RequireStat {
     stat: StatId,
     required: i32,
 }
+impl RequireStat {
+    pub fn new(stat: StatId, required: i32) -> RequireStat {
+        RequireStat {
+            stat: stat,
+            required: required,
+        }
+    }
+}
 impl SkillRequirement for RequireStat {
-    fn satisfies(&self, learner: &Index, parts: &GameParts) -> Option<SkillRejectionReason> {
-        let dif = *parts.get_entity(learner).get_stat(&self.stat).val() - self.required;
+    fn satisfies(&self, learner: &Entity) -> Option<SkillRejectionReason> {
+        let dif = *learner.get_stat(&self.stat).val() - self.required;
         if dif < 0 {
-            Some(SkillRejectionReason::StatTooLow(self, dif))
+            Some(SkillRejectionReason::StatTooLow(self.stat, dif))
+        } else {
+            None
+        }
+    }
+}
+pub struct RequireTrait(pub TraitId);
+impl SkillRequirement for RequireTrait {
+    fn satisfies(&self, learner: &Entity) -> Option<SkillRejectionReason> {
+        if learner.has_trait(&self.0) {
+            None
         } else {
+            Some(SkillRejectionReason::MissingTrait(self.0))
+        }
+    }
+}
+pub struct RequireLevel(pub u32);
+impl SkillRequirement for RequireLevel {
+    fn satisfies(&self, learner: &Entity) -> Option<SkillRejectionReason> {
+        if learner.get_level() >= self.0 {
             None
+        } else {
+            Some(SkillRejectionReason::LevelTooLow(self.0))
+        }
+    }
+}
+pub struct RequireFaction(pub FactionId);
+impl SkillRequirement for RequireFaction {
+    fn satisfies(&self, learner: &Entity) -> Option<SkillRejectionReason> {
+        if *learner.faction() == self.0 {
+            None
+        } else {
+            Some(SkillRejectionReason::WrongFaction(self.0))
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Skill {
-    BasicAttack,
-    Backstab { dmg: i32 },
-    FireBall { dmg: i32 },
+//A learned skill, with its numbers worked out from the caster's stats and mastery of it when it
+//was learned or last ranked up
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Skill {
+    id: SkillId,
+    effect: SkillEffect,
+    target: TargetRule,
+    range: f64,
+    dmg: i32,
+    kind: DamageType,
+    shape: Shape,
+    stamina: i32,
+    mana: i32,
+    cooldown: u64,
+    inflicts: Option<(EffectId, u32)>,
 }
 
 pub fn add_skill<'a>(id: &SkillId, learner: &Index, parts: &mut GameParts) {
     if can_learn(id, parts.get_entity(learner)) {
-        parts.add_skill(learner, create_skill(parts, id, learner));
+        if let Some(skill) = create_skill(parts, id, learner) {
+            parts.add_skill(learner, skill);
+        }
     }
 }
 pub fn can_learn<'a, 'b>(id: &SkillId, _learner: &'a dyn Skillable) -> bool {
@@ -96,54 +288,375 @@ pub fn can_learn<'a, 'b>(id: &SkillId, _learner: &'a dyn Skillable) -> bool {
         SkillId::BasicAttack => true,
         SkillId::Backstab => true,
         SkillId::FireBall => true,
+        SkillId::Custom(_) => true,
     }
 }
-pub fn create_skill(parts: &GameParts, id: &SkillId, entity: &Index) -> Skill {
-    match *id {
-        SkillId::BasicAttack => Skill::BasicAttack,
-        SkillId::Backstab => Skill::Backstab {
-            dmg: parts.get_entity(entity).get_stat(&StatId::Strength).val()
-                + parts.get_entity(entity).get_stat(&StatId::Dexerity).val() / 3,
-        },
-        SkillId::FireBall => Skill::FireBall {
-            dmg: StatId::Willpower.get_val(parts, entity)
-                + parts.get_entity(entity).get_stat(&StatId::Skill).val() / 3,
-        },
-    }
+pub fn create_skill(parts: &GameParts, id: &SkillId, entity: &Index) -> Option<Skill> {
+    let def = parts.get_definitions().skill(id)?;
+    Some(Skill::from_def(*id, def, parts.get_entity(entity)))
 }
 impl Skill {
+    pub fn from_def(id: SkillId, def: &SkillDef, learner: &Entity) -> Skill {
+        let rank = learner.get_mastery(&id) as i32;
+        let cost = |cost: i32| cost * (100 - rank * MASTERY_COST) / 100;
+        Skill {
+            id: id,
+            effect: def.effect,
+            target: def.target,
+            //Melee skills keep to the tiles next to the caster
+            range: if def.range > 1.0 {
+                def.range * (100 + rank * MASTERY_RANGE) as f64 / 100.0
+            } else {
+                def.range
+            },
+            dmg: def.damage.eval_for(learner) * (100 + rank * MASTERY_DAMAGE) / 100,
+            kind: def.kind,
+            shape: def.shape,
+            stamina: cost(def.stamina),
+            mana: cost(def.mana),
+            cooldown: def.cooldown,
+            inflicts: def.inflicts,
+        }
+    }
     pub fn act(&self, parts: &GameParts, events: &mut Events, caster: Index, target: Index) {
-        match self {
-            Skill::BasicAttack => {
-                StatId::Health.event_change_for(
-                    events,
-                    target,
-                    StatId::Strength.get_val(parts, &caster),
-                    StatChangeCause::EntityDamage(caster),
-                );
-                events.push(
-                    5,
-                    Event::ChangeEntity(caster, Change::SkillExp(SkillId::BasicAttack, 1)),
-                );
+        let entity = parts.get_entity(&caster);
+        if !entity.is_ready(&self.id, parts.get_tick()) {
+            return;
+        }
+        if let Some(stat) = self.lacking(entity) {
+            events.character(&caster, CharacterEvent::FailedToCast(self.id, stat));
+            return;
+        }
+        self.pay(parts, events, caster);
+        let mut rng = parts.rng(RngStream::Skills, Some(&caster));
+        let mut landed = false;
+        for victim in self.area(parts, caster, target) {
+            landed |= match self.effect {
+                SkillEffect::Strike | SkillEffect::FireBall => {
+                    self.strike(parts, events, &mut rng, caster, victim, self.dmg)
+                }
+                SkillEffect::Backstab => {
+                    let bonus = match parts
+                        .get_board()
+                        .as_ref()
+                        .and_then(|board| board.side_of(&victim, &caster))
+                    {
+                        Some(Side::Behind) => BACKSTAB_BEHIND,
+                        Some(Side::Flank) => BACKSTAB_FLANK,
+                        Some(Side::Front) | None => 100,
+                    };
+                    self.strike(
+                        parts,
+                        events,
+                        &mut rng,
+                        caster,
+                        victim,
+                        self.dmg * bonus / 100,
+                    )
+                }
+                SkillEffect::Inflict => {
+                    self.inflict(events, caster, victim);
+                    true
+                }
+                SkillEffect::Heal => {
+                    StatId::Health.event_change_for(
+                        events,
+                        victim,
+                        self.dmg,
+                        StatChangeCause::Healing(caster),
+                    );
+                    self.inflict(events, caster, victim);
+                    true
+                }
+                SkillEffect::Cleanse => {
+                    events.push(2, Event::ChangeEntity(victim, Change::Cleanse));
+                    self.inflict(events, caster, victim);
+                    true
+                }
+            };
+        }
+        if landed {
+            events.push(5, Event::ChangeEntity(caster, Change::SkillExp(self.id, 1)));
+            StatId::Experience.event_change_for(
+                events,
+                caster,
+                XP_PER_USE,
+                StatChangeCause::SkillUse(self.id),
+            );
+        }
+        if caster != target {
+            if let Some(board) = parts.get_board() {
+                if let (Some(from), Some(to)) =
+                    (board.get_position(&caster), board.get_position(&target))
+                {
+                    if let Some(dir) = Direction::towards(from, to) {
+                        events.push(2, Event::ChangeBoard(BoardChange::Face(caster, dir)));
+                    }
+                }
+            }
+        }
+    }
+    //Rolls the attack against the victim, reporting the roll to both sides, and hurts
+    //it when the attack lands
+    fn strike(
+        &self,
+        parts: &GameParts,
+        events: &mut Events,
+        rng: &mut Rng,
+        caster: Index,
+        victim: Index,
+        dmg: i32,
+    ) -> bool {
+        let entity = parts.get_entity(&caster);
+        let target = parts.get_entity(&victim);
+        let roll = damage::roll(rng, entity, target);
+        events.character(&caster, CharacterEvent::Rolled(roll, victim));
+        events.character(&victim, CharacterEvent::Rolled(roll, caster));
+        if !roll.landed() {
+            return false;
+        }
+        damage::deal(
+            events,
+            target,
+            damage::compute(
+                parts,
+                self.kind,
+                dmg,
+                roll,
+                Some(entity),
+                target,
+                StatChangeCause::EntityDamage(caster),
+            ),
+        );
+        self.inflict(events, caster, victim);
+        if let Some(board) = parts.get_board() {
+            for ally in board.get_allies(target.faction()) {
+                if *ally != victim {
+                    events.character(ally, CharacterEvent::AllyHit(victim));
+                }
+            }
+        }
+        true
+    }
+    fn inflict(&self, events: &mut Events, caster: Index, victim: Index) {
+        if let Some((effect, duration)) = self.inflicts {
+            events.push(
+                2,
+                Event::ChangeEntity(victim, Change::AddEffect(effect, duration, Some(caster))),
+            );
+        }
+    }
+    //The target and whoever else stands in the skill's shape when aimed at it, as long as
+    //the skill could have been aimed at them too
+    pub fn area(&self, parts: &GameParts, caster: Index, target: Index) -> Vec<Index> {
+        let mut victims = vec![target];
+        if let Some(board) = parts.get_board() {
+            if let (Some(from), Some(at)) =
+                (board.get_position(&caster), board.get_position(&target))
+            {
+                for id in self.shape.inhabitants(board, from, at) {
+                    if id != target && self.can_affect(&caster, id, parts) {
+                        victims.push(id);
+                    }
+                }
             }
-            Skill::Backstab { dmg: _ } => (),
-            Skill::FireBall { dmg: _ } => (),
+        }
+        victims
+    }
+    //True when using the skill on the target would only repeat what is already on it
+    pub fn redundant_on(&self, target: &Entity) -> bool {
+        match self.effect {
+            SkillEffect::Heal => !target.is_injured(),
+            SkillEffect::Cleanse => !target
+                .get_effects()
+                .keys()
+                .any(|effect| effect.is_harmful()),
+            _ => match self.inflicts {
+                Some((effect, _)) => target.has_effect(&effect),
+                None => false,
+            },
+        }
+    }
+    pub fn is_effect_only(&self) -> bool {
+        self.effect == SkillEffect::Inflict
+    }
+    pub fn is_support(&self) -> bool {
+        match self.effect {
+            SkillEffect::Heal => true,
+            SkillEffect::Cleanse => true,
+            _ => false,
+        }
+    }
+    //How much good aiming the skill at the target does. Heals count the Health percentage
+    //missing from everyone they reach, cleanses those with something to wash away and
+    //everything else simply how many it reaches
+    pub fn score(&self, parts: &GameParts, caster: Index, target: Index) -> i32 {
+        let area = self.area(parts, caster, target);
+        match self.effect {
+            SkillEffect::Heal => area
+                .iter()
+                .map(|id| 100 - parts.get_entity(id).health_percent())
+                .sum(),
+            SkillEffect::Cleanse => area
+                .iter()
+                .filter(|id| !self.redundant_on(parts.get_entity(id)))
+                .count() as i32,
+            _ => area.len() as i32,
+        }
+    }
+
+    pub fn id(&self) -> SkillId {
+        self.id
+    }
+    pub fn dmg(&self) -> i32 {
+        self.dmg
+    }
+    pub fn range(&self) -> f64 {
+        self.range
+    }
+    pub fn get_shape(&self) -> Shape {
+        self.shape
+    }
+    pub fn get_cooldown(&self) -> u64 {
+        self.cooldown
+    }
+    pub fn get_stamina(&self) -> i32 {
+        self.stamina
+    }
+    pub fn get_mana(&self) -> i32 {
+        self.mana
+    }
+
+    //The first resource the caster does not have enough of
+    pub fn lacking(&self, caster: &Entity) -> Option<StatId> {
+        if *caster.get_stat(&StatId::Stamina).val() < self.stamina {
+            Some(StatId::Stamina)
+        } else if *caster.get_stat(&StatId::Mana).val() < self.mana {
+            Some(StatId::Mana)
+        } else {
+            None
+        }
+    }
+    pub fn usable(&self, caster: &Entity, tick: u64) -> bool {
+        caster.is_ready(&self.id, tick) && self.lacking(caster).is_none()
+    }
+    fn pay(&self, parts: &GameParts, events: &mut Events, caster: Index) {
+        if self.stamina > 0 {
+            StatId::Stamina.event_change_for(
+                events,
+                caster,
+                -self.stamina,
+                StatChangeCause::SkillCost(self.id),
+            );
+        }
+        if self.mana > 0 {
+            StatId::Mana.event_change_for(
+                events,
+                caster,
+                -self.mana,
+                StatChangeCause::SkillCost(self.id),
+            );
+        }
+        let cooldown = if parts.get_entity(&caster).has_effect(&EffectId::Haste) {
+            self.cooldown / 2
+        } else {
+            self.cooldown
+        };
+        if cooldown > 0 {
+            events.push(
+                2,
+                Event::ChangeEntity(
+                    caster,
+                    Change::Cooldown(self.id, parts.get_tick() + cooldown),
+                ),
+            );
         }
     }
 
     pub fn can_target_self(&self) -> bool {
-        match self {
-            Skill::BasicAttack => false,
-            Skill::Backstab { dmg: _ } => false,
-            Skill::FireBall { dmg: _ } => false,
+        self.target == TargetRule::Myself
+    }
+
+    pub fn can_target(&self, caster: &Index, distance: f64, id: Index, parts: &GameParts) -> bool {
+        distance <= self.range && self.can_affect(caster, id, parts)
+    }
+    pub fn can_affect(&self, caster: &Index, id: Index, parts: &GameParts) -> bool {
+        match self.target {
+            TargetRule::Foe => {
+                *caster != id && !parts.get_entity(caster).allied(parts.get_entity(&id))
+            }
+            TargetRule::Ally => {
+                *caster != id && parts.get_entity(caster).allied(parts.get_entity(&id))
+            }
+            TargetRule::Friend => {
+                *caster == id || parts.get_entity(caster).allied(parts.get_entity(&id))
+            }
+            TargetRule::Myself => *caster == id,
+            TargetRule::Anyone => true,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entities::stats::StatChange;
+    use crate::game::testing::settle;
+    use crate::game::GameLoop;
+
+    #[test]
+    fn mastery_improves_the_skill() {
+        let mut game = GameLoop::with_seed(0);
+        let mut events = Events::new();
+        let id = game.add_entity(&mut events, None);
+        //Enough Willpower for every rank to show in the damage
+        let wise = StatChange::Grow(StatId::Willpower, 14, StatChangeCause::LevelUp);
+        events.push(1, Event::ChangeStat(id, wise));
+        events.push(1, Event::ChangeEntity(id, Change::AddTrait(TraitId::Mage)));
+        settle(&mut game, events);
+        let fireball = |game: &GameLoop| {
+            *game
+                .get_parts()
+                .get_entity(&id)
+                .get_skills()
+                .iter()
+                .find(|skill| skill.id() == SkillId::FireBall)
+                .unwrap()
+        };
+        let untrained = fireball(&game);
+
+        let mut ranks = Vec::new();
+        for exp in vec![MASTERY_RANKS[0], MASTERY_RANKS[1] - MASTERY_RANKS[0]] {
+            let mut events = Events::new();
+            events.push(
+                1,
+                Event::ChangeEntity(id, Change::SkillExp(SkillId::FireBall, exp)),
+            );
+            settle(&mut game, events);
+            ranks.push(fireball(&game));
+        }
+        assert_eq!(
+            game.get_parts()
+                .get_entity(&id)
+                .get_mastery(&SkillId::FireBall),
+            2
+        );
 
-    pub fn can_target(&self, distance: f64, _id: Index, _parts: &GameParts) -> bool {
-        match self {
-            Skill::BasicAttack => distance <= 1f64,
-            Skill::Backstab { dmg: _ } => distance <= 1f64,
-            Skill::FireBall { dmg: _ } => distance <= 3f64,
+        let mut last = untrained;
+        for (rank, skill) in ranks.into_iter().enumerate() {
+            let rank = rank as i32 + 1;
+            assert_eq!(
+                skill.dmg(),
+                untrained.dmg() * (100 + rank * MASTERY_DAMAGE) / 100
+            );
+            assert_eq!(
+                skill.get_mana(),
+                untrained.get_mana() * (100 - rank * MASTERY_COST) / 100
+            );
+            assert!(skill.dmg() > last.dmg());
+            assert!(skill.range() > last.range());
+            assert!(skill.get_mana() < last.get_mana());
+            last = skill;
         }
     }
 }
@@ -1,23 +1,70 @@
+use crate::game::entities::damage::Damage;
+use crate::game::entities::effects::EffectId;
 use crate::game::entities::items::ItemId;
-use crate::game::entities::{CharacterEvent, Entity};
+use crate::game::entities::skills::SkillId;
+use crate::game::entities::traits::TraitId;
+use crate::game::entities::{Cause, CharacterEvent, Entity, NaturalCause};
 use crate::game::{Event, Events, GameParts};
 use generational_arena::Index;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum StatChangeCause {
     ItemEffect(ItemId, usize),
     PassiveRestoration,
     EntityDamage(Index),
     LevelUp,
+    BattleReward,
+    SkillCost(SkillId),
+    //Experience for landing the skill
+    SkillUse(SkillId),
+    //Healed by the given entity
+    Healing(Index),
+    //Experience for killing the given entity
+    Kill(Index),
+    //Granted by a trait when it was gained
+    Trait(TraitId),
+    //A status effect and whoever inflicted it
+    Effect(EffectId, Option<Index>),
+}
+impl StatChangeCause {
+    pub fn attacker(&self) -> Option<Index> {
+        match *self {
+            StatChangeCause::EntityDamage(id) => Some(id),
+            StatChangeCause::Effect(_, source) => source,
+            StatChangeCause::ItemEffect(_, _) => None,
+            StatChangeCause::PassiveRestoration => None,
+            StatChangeCause::LevelUp => None,
+            StatChangeCause::BattleReward => None,
+            StatChangeCause::SkillCost(_) => None,
+            StatChangeCause::SkillUse(_) => None,
+            StatChangeCause::Healing(_) => None,
+            StatChangeCause::Kill(_) => None,
+            StatChangeCause::Trait(_) => None,
+        }
+    }
+    //Who gets the credit when this change kills someone
+    pub fn blame(&self) -> Cause {
+        match self.attacker() {
+            Some(id) => Cause::Agent(id),
+            None => Cause::Natural(NaturalCause::Attrition),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+//Percentage of the experience needed for the last level that the next one takes
+pub const EXPERIENCE_CURVE: i32 = 150;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum StatChange {
     Add(StatId, i32, StatChangeCause),
     Sub(StatId, i32, StatChangeCause),
+    //Health lost to a hit, with how much of it was mitigated along the way
+    Damage(Damage),
+    //Raises the most the stat can be along with the stat itself
+    Grow(StatId, i32, StatChangeCause),
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ModifyEvent {
     Add(StatId, i32, StatChangeCause),
     Sub(StatId, i32, StatChangeCause),
@@ -25,7 +72,7 @@ pub enum ModifyEvent {
     MaxReached(StatId, i32, StatChangeCause),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum StatId {
     Health,
     Stamina,
@@ -63,6 +110,7 @@ impl StatId {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StatSuite {
     hp: Stat,
     stam: Stat,
@@ -77,20 +125,33 @@ pub struct StatSuite {
     exp: Stat,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatTemplate {
+    pub health: i32,
+    pub stamina: i32,
+    pub strength: i32,
+    pub dexerity: i32,
+    pub fortitude: i32,
+    pub mana: i32,
+    pub willpower: i32,
+    pub skill: i32,
+    pub experience: i32,
+}
+
 impl StatSuite {
-    pub fn new() -> StatSuite {
+    pub fn from_template(template: &StatTemplate) -> StatSuite {
         StatSuite {
-            hp: Stat::new(10),
-            stam: Stat::new(100),
-            str: Stat::new(8),
-            dex: Stat::new(8),
-            fort: Stat::new(3),
+            hp: Stat::new(template.health),
+            stam: Stat::new(template.stamina),
+            str: Stat::new(template.strength),
+            dex: Stat::new(template.dexerity),
+            fort: Stat::new(template.fortitude),
 
-            mp: Stat::new(0),
-            will: Stat::new(3),
+            mp: Stat::new(template.mana),
+            will: Stat::new(template.willpower),
 
-            skill_rating: Stat::new(1),
-            exp: Stat::new_disharmonic(0, 15),
+            skill_rating: Stat::new(template.skill),
+            exp: Stat::new_disharmonic(0, template.experience),
         }
     }
 
@@ -100,11 +161,21 @@ impl StatSuite {
             CharacterEvent::ModifyStat(match change {
                 StatChange::Add(stat, value, cause) => self.modify(stat, value, cause),
                 StatChange::Sub(stat, value, cause) => self.modify(stat, value, cause),
+                StatChange::Damage(damage) => {
+                    self.modify(StatId::Health, -damage.total(), damage.get_cause())
+                }
+                StatChange::Grow(stat, value, cause) => {
+                    self.get_mut(&stat).grow(stat, value, cause)
+                }
             }),
         );
     }
     pub fn modify(&mut self, id: StatId, v: i32, cause: StatChangeCause) -> ModifyEvent {
-        self.get_mut(&id).modify(id, v, cause)
+        match id {
+            //Experience past the next level is kept for the ones after it
+            StatId::Experience if v > 0 => self.exp.gain(id, v, cause),
+            _ => self.get_mut(&id).modify(id, v, cause),
+        }
     }
     pub fn get(&self, id: &StatId) -> &Stat {
         match *id {
@@ -119,6 +190,15 @@ impl StatSuite {
             StatId::Experience => &self.exp,
         }
     }
+    //Experience runs out towards the next level, each needing more than the last
+    pub fn level_up(&mut self) -> bool {
+        if *self.exp.max() <= 0 || self.exp.val() < self.exp.max() {
+            return false;
+        }
+        let next = self.exp.max() * EXPERIENCE_CURVE / 100;
+        self.exp.carry_over(next);
+        true
+    }
     pub fn get_mut(&mut self, id: &StatId) -> &mut Stat {
         match *id {
             StatId::Health => &mut self.hp,
@@ -134,6 +214,7 @@ impl StatSuite {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Stat {
     value: i32,
     max_value: i32,
@@ -158,11 +239,31 @@ impl Stat {
     pub fn max(&self) -> &i32 {
         &self.max_value
     }
+    //Starts over towards a new most with whatever went past the old one
+    pub fn carry_over(&mut self, max: i32) {
+        self.value -= self.max_value;
+        self.max_value = max;
+    }
+    //Like modify but never cut off at the most
+    pub fn gain(&mut self, id: StatId, v: i32, cause: StatChangeCause) -> ModifyEvent {
+        self.value += v;
+        if self.value >= self.max_value {
+            ModifyEvent::MaxReached(id, v, cause)
+        } else {
+            ModifyEvent::Add(id, v, cause)
+        }
+    }
+    pub fn grow(&mut self, id: StatId, v: i32, cause: StatChangeCause) -> ModifyEvent {
+        self.max_value += v;
+        self.value += v;
+        ModifyEvent::Add(id, v, cause)
+    }
     pub fn modify(&mut self, id: StatId, v: i32, cause: StatChangeCause) -> ModifyEvent {
+        let previous = self.value;
         self.value += v;
         if v > 0 {
             if self.value >= self.max_value {
-                let dif = self.max_value - (self.value + v);
+                let dif = self.max_value - previous;
                 self.value = self.max_value;
                 ModifyEvent::MaxReached(id, dif, cause)
             } else {
@@ -170,7 +271,7 @@ impl Stat {
             }
         } else {
             if self.value <= 0 {
-                let dif = -v - self.value;
+                let dif = previous;
                 self.value = 0;
                 ModifyEvent::ZeroReached(id, dif, cause)
             } else {
@@ -179,3 +280,66 @@ impl Stat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(experience: i32) -> StatTemplate {
+        StatTemplate {
+            health: 10,
+            stamina: 10,
+            strength: 1,
+            dexerity: 1,
+            fortitude: 1,
+            mana: 0,
+            willpower: 1,
+            skill: 1,
+            experience: experience,
+        }
+    }
+
+    fn gain(stats: &mut StatSuite, xp: i32) {
+        stats.modify(StatId::Experience, xp, StatChangeCause::BattleReward);
+    }
+
+    #[test]
+    fn levels_follow_the_curve() {
+        let mut stats = StatSuite::from_template(&template(20));
+        gain(&mut stats, 19);
+        assert!(!stats.level_up());
+        gain(&mut stats, 1);
+        assert!(stats.level_up());
+        assert!(!stats.level_up());
+        assert_eq!(*stats.get(&StatId::Experience).val(), 0);
+        assert_eq!(
+            *stats.get(&StatId::Experience).max(),
+            20 * EXPERIENCE_CURVE / 100
+        );
+    }
+
+    #[test]
+    fn experience_carries_over_several_levels() {
+        let mut stats = StatSuite::from_template(&template(20));
+        //20, then 30, then 45 for the third level
+        gain(&mut stats, 20 + 30 + 45 + 7);
+        let mut levels = 0;
+        while stats.level_up() {
+            levels += 1;
+        }
+        assert_eq!(levels, 3);
+        assert_eq!(*stats.get(&StatId::Experience).val(), 7);
+        assert_eq!(
+            *stats.get(&StatId::Experience).max(),
+            45 * EXPERIENCE_CURVE / 100
+        );
+    }
+
+    #[test]
+    fn no_levels_without_experience_to_earn() {
+        let mut stats = StatSuite::from_template(&template(0));
+        assert!(!stats.level_up());
+        gain(&mut stats, 5);
+        assert!(!stats.level_up());
+    }
+}
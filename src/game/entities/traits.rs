@@ -1,41 +1,218 @@
-use crate::game::entities::{Change, CharacterEvent, Entity};
-use crate::game::Events;
+use crate::game::definitions::Definitions;
+use crate::game::entities::combat::CombatStance;
+use crate::game::entities::damage::DamageType;
+use crate::game::entities::effects::EffectId;
+use crate::game::entities::factions::{FactionId, Factionable};
+use crate::game::entities::skills::{
+    AlternativeRequirement, EachRequirement, RequireFaction, RequireLevel, RequireStat,
+    RequireTrait, SkillRejectionReason, SkillRequirement,
+};
+use crate::game::entities::stats::{StatChange, StatChangeCause, StatId};
+use crate::game::entities::{Change, CharacterEvent, CharacterEventKind, Entity, State};
+use crate::game::{Event, Events, GameParts, Idable};
+use generational_arena::Index;
 
-#[derive(Hash, Clone, Copy, PartialEq, Eq, Debug)]
+pub const FORTIFIED_TICKS: u32 = 3;
+pub const INSPIRED_TICKS: u32 = 4;
+//How far a Bard's inspiration carries
+pub const INSPIRE_RANGE: f64 = 3.0;
+//Rangers back off from foes this close when they have something to shoot with from further
+pub const KEEP_DISTANCE: f64 = 1.5;
+
+#[derive(Hash, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum TraitId {
     Crusader,
     Mage,
     Priest,
     Ranger,
     Bard,
+    //Crusader and Priest at once
+    Paladin,
 }
 
 pub trait Traitable {
     fn has_trait<'b>(&self, id: &'b TraitId) -> bool;
 }
 
-impl TraitId {
-    pub fn can_gain(self, entity: &Entity) -> bool {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reaction {
+    pub on: CharacterEventKind,
+    pub change: Change,
+}
+
+//What an entity needs before gaining a trait, as written in the definitions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Requirement {
+    //At least the given value
+    Stat(StatId, i32),
+    Trait(TraitId),
+    //At least the given level
+    Level(u32),
+    Faction(FactionId),
+    //All of them
+    Each(Vec<Requirement>),
+    //Any one of them
+    Either(Vec<Requirement>),
+}
+impl Requirement {
+    pub fn build(&self) -> Box<dyn SkillRequirement> {
         match self {
-            TraitId::Crusader => !entity.has_trait(&TraitId::Crusader),
-            TraitId::Mage => !entity.has_trait(&TraitId::Mage),
-            TraitId::Priest => !entity.has_trait(&TraitId::Priest),
-            TraitId::Ranger => !entity.has_trait(&TraitId::Ranger),
-            TraitId::Bard => !entity.has_trait(&TraitId::Bard),
+            Requirement::Stat(stat, required) => Box::new(RequireStat::new(*stat, *required)),
+            Requirement::Trait(trt) => Box::new(RequireTrait(*trt)),
+            Requirement::Level(level) => Box::new(RequireLevel(*level)),
+            Requirement::Faction(faction) => Box::new(RequireFaction(*faction)),
+            Requirement::Each(requirements) => Box::new(EachRequirement::new(
+                requirements.iter().map(|r| r.build()).collect(),
+            )),
+            Requirement::Either(requirements) => Box::new(AlternativeRequirement::new(
+                requirements.iter().map(|r| r.build()).collect(),
+            )),
         }
     }
+}
 
-    pub fn on_gain<'a>(self, _entity: &Entity, _events: &mut Events) {
-        match self {
-            TraitId::Crusader => (),
-            TraitId::Mage => (),
-            TraitId::Priest => (),
-            TraitId::Ranger => (),
-            TraitId::Bard => (),
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraitDef {
+    //All of them have to be met
+    #[serde(default)]
+    pub requires: Vec<Requirement>,
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    //Percentage of each damage type shrugged off, negative for weaknesses
+    #[serde(default)]
+    pub resistances: Vec<(DamageType, i32)>,
+    //Learned when the trait is gained
+    #[serde(default)]
+    pub skills: Vec<String>,
+    //Added on top of the stats when the trait is gained
+    #[serde(default)]
+    pub stats: Vec<(StatId, i32)>,
+    //Added on top of the stats on every level up
+    #[serde(default)]
+    pub growth: Vec<(StatId, i32)>,
+}
+
+impl TraitId {
+    pub fn can_gain(self, entity: &Entity, defs: &Definitions) -> Result<(), SkillRejectionReason> {
+        if entity.has_trait(&self) {
+            return Err(SkillRejectionReason::AlreadyKnown);
+        }
+        match defs.trait_def(&self) {
+            Some(def) => match Requirement::Each(def.requires.clone())
+                .build()
+                .satisfies(entity)
+            {
+                Some(reason) => Err(reason),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    //The stat bonuses go in before the skills so the skills are learned with them
+    pub fn on_gain<'a>(self, entity: &Entity, defs: &Definitions, events: &mut Events) {
+        if let Some(def) = defs.trait_def(&self) {
+            for (stat, bonus) in def.stats.iter() {
+                events.push(
+                    3,
+                    Event::ChangeStat(
+                        entity.id(),
+                        StatChange::Grow(*stat, *bonus, StatChangeCause::Trait(self)),
+                    ),
+                );
+            }
+            for name in def.skills.iter() {
+                if let Some(skill) = defs.skill_id(name) {
+                    events.push(
+                        4,
+                        Event::ChangeEntity(entity.id(), Change::LearnSkill(skill)),
+                    );
+                }
+            }
+        }
+    }
+    pub fn on_level_up<'a>(self, entity: &Entity, defs: &Definitions, events: &mut Events) {
+        if let Some(def) = defs.trait_def(&self) {
+            for (stat, bonus) in def.growth.iter() {
+                events.push(
+                    3,
+                    Event::ChangeStat(
+                        entity.id(),
+                        StatChange::Grow(*stat, *bonus, StatChangeCause::LevelUp),
+                    ),
+                );
+            }
         }
     }
-    pub fn handle<'a>(&self, _entity: &Entity, _event: CharacterEvent) -> Option<Change> {
-        None
+    //The changes the trait asks for, and who for, when its owner sees the event. The
+    //reactions in the definitions come first, then what the class does on its own
+    pub fn handle<'a>(
+        &self,
+        entity: &Entity,
+        event: CharacterEvent,
+        parts: &GameParts,
+    ) -> Vec<(Index, Change)> {
+        let mut changes: Vec<(Index, Change)> = match parts.get_definitions().trait_def(self) {
+            Some(def) => def
+                .reactions
+                .iter()
+                .filter(|reaction| reaction.on == event.kind())
+                .map(|reaction| (entity.id(), reaction.change))
+                .collect(),
+            None => Vec::new(),
+        };
+        let board = match parts.get_board() {
+            Some(board) => board,
+            None => return changes,
+        };
+        match (self, event) {
+            (TraitId::Crusader, CharacterEvent::AllyHit(_)) => changes.push((
+                entity.id(),
+                Change::AddEffect(EffectId::Fortified, FORTIFIED_TICKS, None),
+            )),
+            (TraitId::Ranger, CharacterEvent::Rolled(_, other)) => {
+                let too_close = board
+                    .distance_between(&entity.id(), &other)
+                    .map_or(false, |d| d <= KEEP_DISTANCE);
+                let ranged = entity
+                    .get_skills()
+                    .iter()
+                    .any(|skill| skill.range() > KEEP_DISTANCE);
+                if let State::Fighting { .. } = entity.get_state() {
+                    if too_close && ranged && !entity.allied(parts.get_entity(&other)) {
+                        changes.push((
+                            entity.id(),
+                            Change::State(State::Fighting {
+                                stance: CombatStance::KeepingDistance(other),
+                            }),
+                        ));
+                    }
+                }
+            }
+            (TraitId::Bard, CharacterEvent::Killed(_)) => {
+                let mut inspired: Vec<Index> = board.get_allies(entity.faction()).to_vec();
+                if !inspired.contains(&entity.id()) {
+                    inspired.push(entity.id());
+                }
+                for ally in inspired {
+                    if board
+                        .distance_between(&entity.id(), &ally)
+                        .map_or(false, |d| d <= INSPIRE_RANGE)
+                    {
+                        changes.push((
+                            ally,
+                            Change::AddEffect(
+                                EffectId::Inspired,
+                                INSPIRED_TICKS,
+                                Some(entity.id()),
+                            ),
+                        ));
+                    }
+                }
+            }
+            _ => (),
+        }
+        changes
     }
 }
 
@@ -69,3 +246,81 @@ pub fn create_trait<T: ClassTrait>(name:str)-> T where T:std::marker::Sized {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testing::settle;
+    use crate::game::GameLoop;
+
+    //A level 1 Feline Crusader
+    fn crusader() -> (GameLoop, Index) {
+        let mut game = GameLoop::with_seed(0);
+        let mut events = Events::new();
+        let id = game.add_entity(&mut events, None);
+        game.get_parts_mut().set_faction(&id, FactionId::Feline);
+        events.push(
+            1,
+            Event::ChangeEntity(id, Change::AddTrait(TraitId::Crusader)),
+        );
+        settle(&mut game, events);
+        (game, id)
+    }
+
+    #[test]
+    fn each_requirement_fails_on_any_unmet() {
+        let (game, id) = crusader();
+        let entity = game.get_parts().get_entity(&id);
+        let requirement = Requirement::Each(vec![
+            Requirement::Trait(TraitId::Crusader),
+            Requirement::Level(3),
+            Requirement::Faction(FactionId::Feline),
+        ]);
+        match requirement.build().satisfies(entity) {
+            Some(SkillRejectionReason::LevelTooLow(3)) => (),
+            _ => panic!("a level 1 Crusader is not level 3"),
+        }
+    }
+
+    #[test]
+    fn either_requirement_passes_on_any_met() {
+        let (game, id) = crusader();
+        let entity = game.get_parts().get_entity(&id);
+        let met = Requirement::Either(vec![
+            Requirement::Level(3),
+            Requirement::Faction(FactionId::Feline),
+        ]);
+        assert!(met.build().satisfies(entity).is_none());
+        let unmet = Requirement::Either(vec![
+            Requirement::Trait(TraitId::Mage),
+            Requirement::Level(3),
+        ]);
+        match unmet.build().satisfies(entity) {
+            Some(SkillRejectionReason::MissingTrait(TraitId::Mage)) => (),
+            _ => panic!("the first unmet requirement is reported"),
+        }
+    }
+
+    #[test]
+    fn gaining_a_trait_reports_why_not() {
+        let (game, id) = crusader();
+        let parts = game.get_parts();
+        let entity = parts.get_entity(&id);
+        match TraitId::Paladin.can_gain(entity, parts.get_definitions()) {
+            Err(SkillRejectionReason::MissingTrait(TraitId::Priest)) => (),
+            _ => panic!("Paladins have to be Priests too"),
+        }
+        match TraitId::Crusader.can_gain(entity, parts.get_definitions()) {
+            Err(SkillRejectionReason::AlreadyKnown) => (),
+            _ => panic!("the trait is already there"),
+        }
+        let fortitude = *entity.get_stat(&StatId::Fortitude).val();
+        match Requirement::Stat(StatId::Fortitude, fortitude + 2)
+            .build()
+            .satisfies(entity)
+        {
+            Some(SkillRejectionReason::StatTooLow(StatId::Fortitude, -2)) => (),
+            _ => panic!("the stat falls 2 short"),
+        }
+    }
+}
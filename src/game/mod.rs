@@ -1,49 +1,153 @@
 pub mod battle;
+pub mod definitions;
 pub mod entities;
+pub mod observers;
+pub mod replay;
+pub mod rng;
+pub mod save;
+pub mod snapshots;
+#[cfg(test)]
+pub mod testing;
 
 extern crate generational_arena;
 use generational_arena::{Arena, Index};
+use rayon::prelude::*;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
-use crate::game::battle::system::board::{Board, BoardChange};
+use crate::game::battle::outcome::{BattleResult, Objective, VictoryCondition, XP_FOR_VICTORY};
+use crate::game::battle::system::board::{Board, BoardChange, ConstructionPart};
+use crate::game::battle::turns::TurnOrder;
+use crate::game::battle::Battle;
+use crate::game::definitions::{Definitions, DEFAULT_TEMPLATE};
+use crate::game::entities::combat::CombatStance;
+use crate::game::entities::effects::{EffectId, HASTE_INITIATIVE};
+use crate::game::entities::factions::{FactionId, Factionable};
 use crate::game::entities::skills::Skillable;
 use crate::game::entities::stats::{Stat, StatChange};
 use crate::game::entities::stats::{StatChangeCause, StatId, StatSuite};
-use crate::game::entities::{Change, CharacterEvent, Entity, State};
-use crate::gui::animation::img::Img;
-use crate::gui::resources::Resources;
+use crate::game::entities::{Change, CharacterEvent, CharacterEventKind, Entity, State, VisualId};
+use crate::game::observers::{
+    CharacterListener, EventListener, ListenerFilter, ListenerId, Observers,
+};
+use crate::game::replay::Recording;
+use crate::game::rng::{Rng, RngService, RngStream};
+use crate::game::snapshots::Snapshots;
 
 pub trait Idable {
     fn id(&self) -> Index;
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum Event {
     ChangeEntity(Index, Change),
     ChangeStat(Index, StatChange),
     ChangeBoard(BoardChange),
+    //The player whose turn it is has given their orders
+    EndTurn(Index),
 }
-#[derive(Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    ChangeEntity,
+    ChangeStat,
+    ChangeBoard,
+    EndTurn,
+}
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::ChangeEntity(_, _) => EventKind::ChangeEntity,
+            Event::ChangeStat(_, _) => EventKind::ChangeStat,
+            Event::ChangeBoard(_) => EventKind::ChangeBoard,
+            Event::EndTurn(_) => EventKind::EndTurn,
+        }
+    }
+    pub fn subject(&self) -> Index {
+        match self {
+            Event::ChangeEntity(id, _) => *id,
+            Event::ChangeStat(id, _) => *id,
+            Event::ChangeBoard(BoardChange::MoveEntity(id, _, _)) => *id,
+            Event::ChangeBoard(BoardChange::RemoveEntity(id)) => *id,
+            Event::ChangeBoard(BoardChange::Face(id, _)) => *id,
+            Event::EndTurn(id) => *id,
+        }
+    }
+}
+pub const PRIORITY_TIERS: usize = 5;
+//Smallest number of entities a pumping thread is handed, below this threads cost more than they save
+const PUMP_CHUNK: usize = 64;
+
+//How many events of each priority tier get applied per tick, the rest carry over to the next one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EventBudget {
+    //None for tiers that are drained completely
+    tiers: [Option<usize>; PRIORITY_TIERS],
+}
+impl EventBudget {
+    pub fn new(tiers: [usize; PRIORITY_TIERS]) -> EventBudget {
+        let mut limits = [None; PRIORITY_TIERS];
+        for (limit, tier) in limits.iter_mut().zip(tiers.iter()) {
+            *limit = Some(*tier);
+        }
+        EventBudget { tiers: limits }
+    }
+    pub fn unlimited() -> EventBudget {
+        EventBudget {
+            tiers: [None; PRIORITY_TIERS],
+        }
+    }
+    pub fn get(&self, pri: u8) -> usize {
+        self.tiers[pri as usize - 1].unwrap_or(usize::max_value())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Events {
-    events: (Vec<Event>, Vec<Event>, Vec<Event>, Vec<Event>, Vec<Event>),
-    character_events: HashMap<Index, Vec<CharacterEvent>>,
+    events: [Vec<Event>; PRIORITY_TIERS],
+    character_events: BTreeMap<Index, Vec<CharacterEvent>>,
 }
 impl Events {
     pub fn new() -> Events {
         Events {
-            events: (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()),
-            character_events: HashMap::new(),
+            events: Default::default(),
+            character_events: BTreeMap::new(),
         }
     }
+    //Priorities outside 1 to 5 are clamped to the nearest tier
     pub fn push(&mut self, pri: u8, event: Event) {
-        match pri {
-            1 => self.events.0.push(event),
-            2 => self.events.1.push(event),
-            3 => self.events.2.push(event),
-            4 => self.events.3.push(event),
-            5 => self.events.4.push(event),
-            _ => unimplemented!(),
+        self.events[Events::tier(pri)].push(event);
+    }
+    pub fn pending(&self, pri: u8) -> usize {
+        self.events[Events::tier(pri)].len()
+    }
+    fn tier(pri: u8) -> usize {
+        std::cmp::min(std::cmp::max(pri as usize, 1), PRIORITY_TIERS) - 1
+    }
+    //Whether the player's orders are already on their way
+    pub fn ends_turn(&self, id: &Index) -> bool {
+        self.events[0].iter().any(|event| match event {
+            Event::EndTurn(ending) => ending == id,
+            _ => false,
+        })
+    }
+    pub fn is_empty(&self) -> bool {
+        self.events.iter().all(|tier| tier.is_empty()) && self.character_events.is_empty()
+    }
+    pub fn merge(&mut self, mut other: Events) {
+        for tier in 0..PRIORITY_TIERS {
+            self.events[tier].append(&mut other.events[tier]);
         }
+        for (id, mut char_events) in other.character_events {
+            self.character_events
+                .entry(id)
+                .or_insert_with(Vec::new)
+                .append(&mut char_events);
+        }
+    }
+    pub fn all_character_events(
+        &self,
+    ) -> std::collections::btree_map::Iter<Index, Vec<CharacterEvent>> {
+        self.character_events.iter()
     }
     pub fn character(&mut self, id: &Index, event: CharacterEvent) {
         if let Some(events) = self.character_events.get_mut(id) {
@@ -55,78 +159,209 @@ impl Events {
         }
     }
 
-    pub fn step(&mut self, parts: &mut GameParts) -> Events {
+    //Drains the tiers from highest priority (1) to lowest (5), each up to its budget.
+    //Whatever a budget leaves behind is queued ahead of the events this step produces.
+    pub fn step(&mut self, parts: &mut GameParts, budget: &EventBudget) -> Events {
         let mut new_events = Events::new();
-        let mut handled = 1;
-        let mut previously_handled = 0;
-
-        let mut index = (0, 0, 0, 0, 0);
-        while handled > previously_handled && handled < 50 {
-            previously_handled = handled;
-            if index.0 == 0 && self.events.0.len() != 0 {
-                handled += self.events.0.len();
-                index.0 = self.events.0.len();
-                for event in self.events.0.iter() {
-                    parts.push(*event, &mut new_events);
-                }
-            } else {
-                if index.1 < self.events.1.len() {
-                    if index.1 + 50 < self.events.1.len() {
-                        for i in index.1..index.1 + 50 {
-                            parts.push(self.events.1[i], &mut new_events);
-                        }
-                        handled += 50;
-                    } else {
-                        for i in index.1..self.events.1.len() {
-                            parts.push(self.events.1[i], &mut new_events);
-                        }
-                        handled += self.events.1.len() - index.1;
-                    }
-                }
+        for tier in 0..PRIORITY_TIERS {
+            let take = std::cmp::min(budget.get(tier as u8 + 1), self.events[tier].len());
+            for event in self.events[tier].drain(..take) {
+                parts.push(event, &mut new_events);
             }
         }
+        for tier in 0..PRIORITY_TIERS {
+            let mut carried = std::mem::replace(&mut self.events[tier], Vec::new());
+            carried.append(&mut new_events.events[tier]);
+            new_events.events[tier] = carried;
+        }
         new_events
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GameParts {
     board: Option<Board>,
+    battle: Option<Battle>,
     entities: Arena<Entity>,
+    rng: RngService,
+    budget: EventBudget,
+
+    //Definitions come from the assets, not the save, set them again after loading a game
+    #[serde(skip)]
+    definitions: Arc<Definitions>,
+    #[serde(skip)]
+    applied: Vec<Event>,
 }
 impl GameParts {
+    pub fn new(seed: u64) -> GameParts {
+        GameParts {
+            board: None,
+            battle: None,
+            entities: Arena::new(),
+            rng: RngService::new(seed),
+            budget: EventBudget::unlimited(),
+
+            definitions: Arc::new(Definitions::builtin()),
+            applied: Vec::new(),
+        }
+    }
     pub fn tick<'a>(&mut self, mut events: Events) -> Events {
-        let mut new_events = events.step(self);
-        for (_, entity) in self.entities.iter() {
-            entity.pump(
-                match events.character_events.get_mut(&entity.id()) {
-                    Some(cevs) => {
-                        cevs.push(CharacterEvent::Tick);
-                        cevs.to_vec()
-                    }
-                    None => vec![CharacterEvent::Tick],
-                },
-                &mut new_events,
-            );
+        self.applied.clear();
+        let budget = self.budget;
+        let mut new_events = events.step(self, &budget);
+        if let Some(battle) = &mut self.battle {
+            battle.record(&new_events, self.rng.tick());
+        }
+
+        //Every entity pumps into its own buffer, the buffers are then merged in arena
+        //order so the result does not depend on how the threads were scheduled
+        let parts: &GameParts = self;
+        let entities: Vec<(Index, &Entity)> = parts.entities.iter().collect();
+        let buffers: Vec<Events> = entities
+            .par_iter()
+            .with_min_len(PUMP_CHUNK)
+            .map(|(id, entity)| {
+                let mut char_events = match events.character_events.get(id) {
+                    Some(cevs) => cevs.to_vec(),
+                    None => Vec::new(),
+                };
+                char_events.push(CharacterEvent::Tick);
+                let mut buffer = Events::new();
+                entity.pump(parts, char_events, &mut buffer);
+                buffer
+            })
+            .collect();
+        for buffer in buffers {
+            new_events.merge(buffer);
+        }
+        if self.awaiting_input().is_none() {
+            self.end_turn();
+        }
+        self.rng.advance();
+        new_events
+    }
+    pub fn replay_tick(&mut self, steps: &[Event]) -> Events {
+        self.applied.clear();
+        let mut new_events = Events::new();
+        for step in steps {
+            self.push(*step, &mut new_events);
+        }
+        if let Some(battle) = &mut self.battle {
+            battle.record(&new_events, self.rng.tick());
+        }
+        if self.awaiting_input().is_none() {
+            self.end_turn();
         }
+        self.rng.advance();
         new_events
     }
     pub fn push(&mut self, step: Event, new_events: &mut Events) {
+        self.applied.push(step);
         match step {
-            Event::ChangeEntity(id, change) => self.entities[id].change(change, new_events),
+            Event::ChangeEntity(id, change) => {
+                let defs = self.definitions.clone();
+                self.entities[id].change(change, &defs, new_events)
+            }
             Event::ChangeStat(id, change) => self.entities[id].change_stat(&id, change, new_events),
             Event::ChangeBoard(change) => {
                 if let Some(b) = &mut self.board {
                     b.change(change, new_events)
                 }
             }
+            Event::EndTurn(id) => {
+                if self.awaiting_input() == Some(id) {
+                    self.end_turn();
+                }
+            }
         }
     }
     pub fn get_entity(&self, id: &Index) -> &Entity {
         &self.entities[*id]
     }
+    pub fn get_applied(&self) -> &Vec<Event> {
+        &self.applied
+    }
+    pub fn get_definitions(&self) -> &Definitions {
+        &self.definitions
+    }
+    pub fn set_definitions(&mut self, definitions: Definitions) {
+        self.definitions = Arc::new(definitions);
+    }
+    pub fn get_tick(&self) -> u64 {
+        self.rng.tick()
+    }
+    pub fn set_event_budget(&mut self, budget: EventBudget) {
+        self.budget = budget;
+    }
+    pub fn rng(&self, stream: RngStream, id: Option<&Index>) -> Rng {
+        self.rng.stream(stream, id)
+    }
+    pub fn get_entities(&self) -> generational_arena::Iter<Entity> {
+        self.entities.iter()
+    }
     pub fn get_board(&self) -> &Option<Board> {
         &self.board
     }
+    pub fn set_board(&mut self, board: Option<Board>) {
+        self.board = board;
+    }
+    pub fn get_battle(&self) -> &Option<Battle> {
+        &self.battle
+    }
+    //Participants still standing, most dexterous first, ties go to whoever was created first
+    pub fn initiative(&self) -> Vec<Index> {
+        let mut standing: Vec<Index> = match &self.battle {
+            Some(battle) => battle
+                .participants()
+                .filter(|id| match self.entities[**id].get_state() {
+                    State::Dead(_) => false,
+                    _ => true,
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        standing.sort_by_key(|id| {
+            let entity = &self.entities[*id];
+            let mut speed = *entity.get_stat(&StatId::Dexerity).val();
+            if entity.has_effect(&EffectId::Haste) {
+                speed += HASTE_INITIATIVE;
+            }
+            (std::cmp::Reverse(speed), *id)
+        });
+        standing
+    }
+    //In real time everyone acts every tick, in turns only the combatant whose turn it is
+    pub fn may_act(&self, id: &Index) -> bool {
+        match self.battle.as_ref().and_then(Battle::get_turns) {
+            Some(turns) => turns.current() == Some(*id) && !turns.is_player(id),
+            None => true,
+        }
+    }
+    //Status effects run every tick in real time, in turns once a round on their owner's own turn.
+    //Players take theirs through GameLoop::take_turn, nothing runs while they decide
+    pub fn effects_due(&self, id: &Index) -> bool {
+        match self.battle.as_ref().and_then(Battle::get_turns) {
+            Some(turns) => turns.current() == Some(*id) && self.awaiting_input().is_none(),
+            None => true,
+        }
+    }
+    pub fn awaiting_input(&self) -> Option<Index> {
+        self.battle
+            .as_ref()
+            .and_then(Battle::get_turns)?
+            .awaiting_input()
+            .filter(|id| self.entities[*id].can_act())
+    }
+    fn end_turn(&mut self) {
+        let standing = self.initiative();
+        if let Some(turns) = self.battle.as_mut().and_then(Battle::get_turns_mut) {
+            turns.advance(standing);
+        }
+    }
+    pub fn set_faction(&mut self, id: &Index, faction: FactionId) {
+        self.entities[*id].set_faction(faction);
+    }
     pub fn modify_stat<'a>(
         &mut self,
         events: &mut Events,
@@ -147,17 +382,36 @@ impl GameParts {
 pub struct GameLoop {
     //executors: Vec<Box<dyn Executable<'b>>>,
     parts: GameParts,
+    recording: Option<Recording>,
+    observers: Observers,
+    snapshots: Snapshots,
+    snapshot_interval: u64,
+    objectives: Vec<Box<dyn Objective>>,
+    //When the battle the objectives were added for started, they outlive it in case a
+    //rollback takes the game back into it
+    objectives_for: Option<u64>,
+    battle_result: Option<BattleResult>,
 
     tick_debt: u128,
 }
 impl GameLoop {
     pub fn new() -> GameLoop {
+        GameLoop::with_seed(0)
+    }
+    pub fn with_seed(seed: u64) -> GameLoop {
+        GameLoop::from_parts(GameParts::new(seed))
+    }
+    pub fn from_parts(parts: GameParts) -> GameLoop {
         GameLoop {
             //executors: Vec::new(),
-            parts: GameParts {
-                board: None,
-                entities: Arena::new(),
-            },
+            parts: parts,
+            recording: None,
+            observers: Observers::new(),
+            snapshots: Snapshots::new(0),
+            snapshot_interval: 0,
+            objectives: Vec::new(),
+            objectives_for: None,
+            battle_result: None,
 
             tick_debt: 0u128,
         }
@@ -173,13 +427,33 @@ impl GameLoop {
             events
         }
     }
+    pub fn execute_once(game: &mut GameLoop, events: Events) -> Events {
+        GameLoop::execute_loop(game, 1, events)
+    }
     fn execute_loop<'a>(game: &mut GameLoop, ticks: u128, mut events: Events) -> Events {
         if ticks == 0 {
             return events;
         }
 
         for _ in 0..ticks {
+            //Nothing left to play out until the player gives their orders
+            //and the time spent waiting is not owed afterwards
+            if game.parts.awaiting_input().is_some() && events.is_empty() {
+                game.tick_debt = 0;
+                break;
+            }
             events = GameParts::tick(&mut game.parts, events);
+            if let Some(recording) = &mut game.recording {
+                recording.record(game.parts.get_applied());
+            }
+            game.observers.notify(&game.parts, &events);
+            if game.snapshot_interval > 0 && game.parts.get_tick() % game.snapshot_interval == 0 {
+                game.snapshots
+                    .take(&game.parts, &events, &game.battle_result);
+            }
+            if let Some(winner) = game.decide_battle() {
+                game.conclude_battle(winner, &mut events);
+            }
             if game.tick_debt > 0 {
                 game.tick_debt -= 1;
             }
@@ -187,16 +461,406 @@ impl GameLoop {
         events
     }
 
-    pub fn add_entity(
+    pub fn add_entity(&mut self, events: &mut Events, visual: Option<VisualId>) -> Index {
+        self.add_entity_from_template(events, visual, DEFAULT_TEMPLATE)
+            .expect("definitions always have a default stat template")
+    }
+    pub fn add_entity_from_template(
         &mut self,
         events: &mut Events,
-        res: &Resources,
-        gl: &gl::Gl,
-    ) -> Result<(), failure::Error> {
-        let entity = Entity::new(StatSuite::new(), Img::new(res, gl)?);
+        visual: Option<VisualId>,
+        template: &str,
+    ) -> Option<Index> {
+        let stats = StatSuite::from_template(self.parts.definitions.stat_template(template)?);
+        let entity = Entity::new(stats, visual);
         let id = self.parts.entities.insert(entity);
         self.parts.entities[id].set_id(id);
         events.push(1, Event::ChangeEntity(id, Change::State(State::Birth)));
+        Some(id)
+    }
+    pub fn add_character_listener(
+        &mut self,
+        filter: ListenerFilter<CharacterEventKind>,
+        listener: Box<dyn CharacterListener>,
+    ) -> ListenerId {
+        self.observers.add_character_listener(filter, listener)
+    }
+    pub fn add_event_listener(
+        &mut self,
+        filter: ListenerFilter<EventKind>,
+        listener: Box<dyn EventListener>,
+    ) -> ListenerId {
+        self.observers.add_event_listener(filter, listener)
+    }
+    pub fn remove_listener(&mut self, id: ListenerId) {
+        self.observers.remove(id);
+    }
+    //Take a snapshot every interval ticks, keeping the last capacity of them around
+    pub fn keep_snapshots(&mut self, interval: u64, capacity: usize) {
+        self.snapshot_interval = interval;
+        self.snapshots = Snapshots::new(capacity);
+    }
+    pub fn snapshot(&mut self, events: &Events) {
+        self.snapshots
+            .take(&self.parts, events, &self.battle_result);
+    }
+    pub fn get_snapshots(&self) -> &Snapshots {
+        &self.snapshots
+    }
+    //Returns to the newest snapshot at least ticks old, handing back the events to carry on with
+    pub fn rollback(&mut self, ticks: u64) -> Option<Events> {
+        let now = self.parts.get_tick();
+        let snapshot = self.snapshots.rewind_to(now.saturating_sub(ticks))?;
+        let rewound = (now - snapshot.tick()) as usize;
+        if let Some(recording) = &mut self.recording {
+            if !recording.rewind(rewound) {
+                self.recording = None;
+            }
+        }
+        let (parts, events, battle_result) = snapshot.restore();
+        self.parts = parts;
+        self.battle_result = battle_result;
+        self.tick_debt = 0;
+        Some(events)
+    }
+
+    //Places the combatants on the board and sends them into combat, once the battle is
+    //over end_battle puts everyone still standing back to what they were doing before.
+    //Start recording after this, the board itself is not part of the recorded events.
+    pub fn start_battle(
+        &mut self,
+        events: &mut Events,
+        mut board: Board,
+        placement: Vec<ConstructionPart>,
+    ) -> Result<(), battle::Error> {
+        if self.parts.battle.is_some() {
+            return Err(battle::Error::AlreadyFighting);
+        }
+        let mut battle = Battle::new(self.parts.get_tick());
+        let mut taken = Vec::new();
+        for part in placement.iter() {
+            let pos = part.pos();
+            match board.get(pos) {
+                None => return Err(battle::Error::OffBoard { pos: pos }),
+                Some(tile) if tile.get().is_some() || taken.contains(&pos) => {
+                    return Err(battle::Error::TileTaken { pos: pos })
+                }
+                Some(_) => taken.push(pos),
+            }
+            if let Some(id) = part.combatant() {
+                let entity = self
+                    .parts
+                    .entities
+                    .get(id)
+                    .ok_or(battle::Error::UnknownEntity { id: id })?;
+                if battle.is_participant(&id) {
+                    return Err(battle::Error::PlacedTwice { id: id });
+                }
+                battle.join(id, *entity.get_state(), *entity.faction());
+            }
+        }
+
+        board.add_parts(&self.parts, placement);
+        for id in battle.participants() {
+            events.push(
+                1,
+                Event::ChangeEntity(
+                    *id,
+                    Change::State(State::Fighting {
+                        stance: CombatStance::FindingTarget,
+                    }),
+                ),
+            );
+        }
+        self.objectives.clear();
+        self.objectives_for = Some(battle.get_started_at());
+        self.parts.board = Some(board);
+        self.parts.battle = Some(battle);
         Ok(())
     }
+    //Clears the board and returns the participants that are still alive
+    pub fn end_battle(&mut self, events: &mut Events) -> Option<Vec<Index>> {
+        let battle = self.parts.battle.take()?;
+        self.parts.board = None;
+        let mut survivors = Vec::new();
+        for id in battle.participants() {
+            if let Some(entity) = self.parts.entities.get(*id) {
+                if let State::Dead(_) = entity.get_state() {
+                    continue;
+                }
+                let previous = *battle.previous_state(id).unwrap();
+                events.push(1, Event::ChangeEntity(*id, Change::State(previous)));
+                survivors.push(*id);
+            }
+        }
+        Some(survivors)
+    }
+    //Switches the battle being fought over to turns in initiative order, the players
+    //give their orders through take_turn whenever awaiting_input names one of them
+    pub fn play_in_turns(&mut self, players: Vec<Index>) -> bool {
+        let initiative = self.parts.initiative();
+        match &mut self.parts.battle {
+            Some(battle) => {
+                battle.set_turns(Some(TurnOrder::new(initiative, players)));
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn play_in_real_time(&mut self) -> bool {
+        match &mut self.parts.battle {
+            Some(battle) => {
+                battle.set_turns(None);
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn awaiting_input(&self) -> Option<Index> {
+        self.parts.awaiting_input()
+    }
+    //Plays the stance for the player whose turn it is, exactly as the AI would have
+    pub fn take_turn(
+        &mut self,
+        events: &mut Events,
+        stance: CombatStance,
+    ) -> Result<Index, battle::Error> {
+        let id = self
+            .parts
+            .awaiting_input()
+            .filter(|id| !events.ends_turn(id))
+            .ok_or(battle::Error::NotWaiting)?;
+        let entity = self.parts.get_entity(&id);
+        let board = self.parts.get_board().as_ref().unwrap();
+        let stance = match stance {
+            CombatStance::FindingTarget => Some(stance),
+            CombatStance::MovingCloser(target, _) | CombatStance::KeepingDistance(target) => {
+                board.distance_between(&id, &target).map(|_| stance)
+            }
+            //Only skills the entity knows, with its own numbers, on targets within reach
+            CombatStance::UsingSkill(skill, target) => entity
+                .get_skills()
+                .iter()
+                .find(|known| known.id() == skill.id())
+                .filter(|known| match board.distance_between(&id, &target) {
+                    Some(d) => known.can_target(&id, d, target, &self.parts),
+                    None => false,
+                })
+                .map(|known| CombatStance::UsingSkill(*known, target)),
+        }
+        .ok_or(battle::Error::InvalidOrder { id: id })?;
+        entity.pump_effects(&self.parts, events);
+        stance.pump(entity, &self.parts, events);
+        //Through the events so recordings see the turn end where it did
+        events.push(1, Event::EndTurn(id));
+        Ok(id)
+    }
+    //Only applies to the battle being fought, every battle starts out with LastStanding
+    pub fn add_victory_condition(&mut self, condition: VictoryCondition) -> bool {
+        match &mut self.parts.battle {
+            Some(battle) => {
+                battle.add_condition(condition);
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn add_objective(&mut self, objective: Box<dyn Objective>) -> bool {
+        if self.parts.battle.is_some() {
+            self.objectives.push(objective);
+            true
+        } else {
+            false
+        }
+    }
+    fn decide_battle(&mut self) -> Option<Option<FactionId>> {
+        let battle = self.parts.battle.as_ref()?;
+        if self.objectives_for == Some(battle.get_started_at()) {
+            for objective in self.objectives.iter_mut() {
+                if let Some(winner) = objective.check(&self.parts) {
+                    return Some(winner);
+                }
+            }
+        }
+        battle.decide(&self.parts)
+    }
+    //Hands out the victory bonus, kills were paid for as they happened, ends the battle and
+    //keeps the result until it is taken
+    fn conclude_battle(&mut self, winner: Option<FactionId>, events: &mut Events) {
+        let result = match &self.parts.battle {
+            Some(battle) => battle.conclude(winner, &self.parts),
+            None => return,
+        };
+        self.end_battle(events);
+        for id in result.get_survivors() {
+            if Some(result.get_combatants()[&id].faction()) == winner {
+                StatId::Experience.event_change_for(
+                    events,
+                    id,
+                    XP_FOR_VICTORY,
+                    StatChangeCause::BattleReward,
+                );
+            }
+        }
+        self.battle_result = Some(result);
+    }
+    pub fn get_battle_result(&self) -> &Option<BattleResult> {
+        &self.battle_result
+    }
+    pub fn take_battle_result(&mut self) -> Option<BattleResult> {
+        self.battle_result.take()
+    }
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new(self.parts.clone()));
+    }
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+    pub fn get_parts(&self) -> &GameParts {
+        &self.parts
+    }
+    pub fn get_parts_mut(&mut self) -> &mut GameParts {
+        &mut self.parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entities::traits::TraitId;
+    use crate::game::testing::{settle, skirmish};
+
+    //A game with one entity and nothing left to apply
+    fn single() -> (GameLoop, Index) {
+        let mut game = GameLoop::with_seed(0);
+        let mut events = Events::new();
+        let id = game.add_entity(&mut events, None);
+        settle(&mut game, events);
+        (game, id)
+    }
+
+    fn restore(id: Index, v: i32) -> Event {
+        Event::ChangeStat(
+            id,
+            StatChange::Add(StatId::Health, v, StatChangeCause::PassiveRestoration),
+        )
+    }
+
+    #[test]
+    fn budget_limits_each_tier() {
+        let (mut game, id) = single();
+        let parts = game.get_parts_mut();
+        let mut events = Events::new();
+        for _ in 0..3 {
+            events.push(1, restore(id, 0));
+            events.push(2, restore(id, 0));
+        }
+        let before = parts.get_applied().len();
+        let left = events.step(parts, &EventBudget::new([2, 1, 0, 0, 0]));
+        assert_eq!(parts.get_applied().len() - before, 3);
+        assert_eq!(left.pending(1), 1);
+        assert_eq!(left.pending(2), 2);
+    }
+
+    #[test]
+    fn priorities_outside_the_tiers_are_clamped() {
+        let (_, id) = single();
+        let mut events = Events::new();
+        events.push(0, restore(id, 0));
+        events.push(9, restore(id, 0));
+        assert_eq!(events.pending(1), 1);
+        assert_eq!(events.pending(5), 1);
+    }
+
+    #[test]
+    fn leftovers_go_ahead_of_new_events() {
+        let (mut game, id) = single();
+        let parts = game.get_parts_mut();
+        let mut events = Events::new();
+        //Gaining the trait queues its stat bonuses on tier 3
+        events.push(
+            1,
+            Event::ChangeEntity(id, Change::AddTrait(TraitId::Crusader)),
+        );
+        events.push(3, restore(id, 1));
+        events.push(3, restore(id, 2));
+        let budget = EventBudget::new([1, 1, 1, 1, 1]);
+        let mut events = events.step(parts, &budget);
+        assert!(events.pending(3) > 1);
+
+        let before = parts.get_applied().len();
+        events.step(parts, &budget);
+        let next = ron::ser::to_string(&parts.get_applied()[before]).unwrap();
+        assert_eq!(next, ron::ser::to_string(&restore(id, 2)).unwrap());
+    }
+
+    //Combatants nobody has given a trait, so they are all as quick as each other
+    fn lineup(count: usize) -> (GameLoop, Events, Vec<Index>) {
+        let mut game = GameLoop::with_seed(0);
+        let mut events = Events::new();
+        let mut construction = Vec::new();
+        let mut ids = Vec::new();
+        for x in 0..count {
+            let id = game.add_entity(&mut events, None);
+            let faction = if x % 2 == 0 {
+                FactionId::Feline
+            } else {
+                FactionId::Rat
+            };
+            game.get_parts_mut().set_faction(&id, faction);
+            construction.push(ConstructionPart::new((x, 0), Some(id)));
+            ids.push(id);
+        }
+        game.start_battle(&mut events, Board::new(6, 6), construction)
+            .unwrap();
+        (game, events, ids)
+    }
+
+    #[test]
+    fn initiative_ties_go_to_the_lower_id() {
+        let (mut game, mut events, ids) = lineup(3);
+        assert_eq!(game.get_parts().initiative(), ids);
+
+        events.push(
+            1,
+            Event::ChangeEntity(ids[2], Change::AddEffect(EffectId::Haste, 5, None)),
+        );
+        events = game.get_parts_mut().tick(events);
+        game.get_parts_mut().tick(events);
+        assert_eq!(game.get_parts().initiative(), vec![ids[2], ids[0], ids[1]]);
+    }
+
+    #[test]
+    fn players_hold_up_the_battle_until_given_orders() {
+        let (mut game, events) = skirmish(1);
+        let mut events = GameLoop::execute_once(&mut game, events);
+        let player = game.get_parts().initiative()[0];
+        game.play_in_turns(vec![player]);
+        assert_eq!(game.awaiting_input(), Some(player));
+        while !events.is_empty() {
+            events = GameLoop::execute_once(&mut game, events);
+        }
+
+        let tick = game.get_parts().get_tick();
+        events = GameLoop::execute(&mut game, &100, events);
+        assert_eq!(game.get_parts().get_tick(), tick);
+        assert_eq!(game.tick_debt, 0);
+
+        assert!(game
+            .take_turn(&mut events, CombatStance::FindingTarget)
+            .is_ok());
+        assert!(game
+            .take_turn(&mut events, CombatStance::FindingTarget)
+            .is_err());
+        GameLoop::execute_once(&mut game, events);
+        let turns = game
+            .get_parts()
+            .get_battle()
+            .as_ref()
+            .unwrap()
+            .get_turns()
+            .unwrap();
+        assert_ne!(turns.current(), Some(player));
+        assert_eq!(game.get_parts().get_tick(), tick + 1);
+    }
 }
@@ -0,0 +1,125 @@
+use generational_arena::Index;
+
+use crate::game::entities::{CharacterEvent, CharacterEventKind};
+use crate::game::{Event, EventKind, Events, GameParts};
+
+pub trait CharacterListener {
+    fn on_character_event(&mut self, id: &Index, event: &CharacterEvent, parts: &GameParts);
+}
+impl<F: FnMut(&Index, &CharacterEvent, &GameParts)> CharacterListener for F {
+    fn on_character_event(&mut self, id: &Index, event: &CharacterEvent, parts: &GameParts) {
+        self(id, event, parts)
+    }
+}
+
+pub trait EventListener {
+    fn on_event(&mut self, event: &Event, parts: &GameParts);
+}
+impl<F: FnMut(&Event, &GameParts)> EventListener for F {
+    fn on_event(&mut self, event: &Event, parts: &GameParts) {
+        self(event, parts)
+    }
+}
+
+//An empty list means no restriction, so ListenerFilter::any() lets everything through
+pub struct ListenerFilter<K> {
+    entities: Vec<Index>,
+    kinds: Vec<K>,
+}
+impl<K: PartialEq> ListenerFilter<K> {
+    pub fn any() -> ListenerFilter<K> {
+        ListenerFilter {
+            entities: Vec::new(),
+            kinds: Vec::new(),
+        }
+    }
+    pub fn new(entities: Vec<Index>, kinds: Vec<K>) -> ListenerFilter<K> {
+        ListenerFilter {
+            entities: entities,
+            kinds: kinds,
+        }
+    }
+    pub fn entity(id: Index) -> ListenerFilter<K> {
+        ListenerFilter::new(vec![id], Vec::new())
+    }
+    pub fn kind(kind: K) -> ListenerFilter<K> {
+        ListenerFilter::new(Vec::new(), vec![kind])
+    }
+    pub fn accepts(&self, id: &Index, kind: &K) -> bool {
+        (self.entities.is_empty() || self.entities.contains(id))
+            && (self.kinds.is_empty() || self.kinds.contains(kind))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(usize);
+
+pub struct Observers {
+    next_id: usize,
+    character_listeners: Vec<(
+        ListenerId,
+        ListenerFilter<CharacterEventKind>,
+        Box<dyn CharacterListener>,
+    )>,
+    event_listeners: Vec<(
+        ListenerId,
+        ListenerFilter<EventKind>,
+        Box<dyn EventListener>,
+    )>,
+}
+impl Observers {
+    pub fn new() -> Observers {
+        Observers {
+            next_id: 0,
+            character_listeners: Vec::new(),
+            event_listeners: Vec::new(),
+        }
+    }
+    pub fn add_character_listener(
+        &mut self,
+        filter: ListenerFilter<CharacterEventKind>,
+        listener: Box<dyn CharacterListener>,
+    ) -> ListenerId {
+        let id = self.next_listener_id();
+        self.character_listeners.push((id, filter, listener));
+        id
+    }
+    pub fn add_event_listener(
+        &mut self,
+        filter: ListenerFilter<EventKind>,
+        listener: Box<dyn EventListener>,
+    ) -> ListenerId {
+        let id = self.next_listener_id();
+        self.event_listeners.push((id, filter, listener));
+        id
+    }
+    pub fn remove(&mut self, id: ListenerId) {
+        self.character_listeners.retain(|(i, _, _)| *i != id);
+        self.event_listeners.retain(|(i, _, _)| *i != id);
+    }
+    fn next_listener_id(&mut self) -> ListenerId {
+        self.next_id += 1;
+        ListenerId(self.next_id)
+    }
+
+    //Called once a tick has finished: first every event that was applied, in the order
+    //it was applied, then the character events the tick produced, in entity order
+    pub fn notify(&mut self, parts: &GameParts, events: &Events) {
+        for event in parts.get_applied() {
+            for (_, filter, listener) in self.event_listeners.iter_mut() {
+                if filter.accepts(&event.subject(), &event.kind()) {
+                    listener.on_event(event, parts);
+                }
+            }
+        }
+        for (id, char_events) in events.all_character_events() {
+            for char_event in char_events {
+                for (_, filter, listener) in self.character_listeners.iter_mut() {
+                    if filter.accepts(id, &char_event.kind()) {
+                        listener.on_character_event(id, char_event, parts);
+                    }
+                }
+            }
+        }
+    }
+}
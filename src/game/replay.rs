@@ -0,0 +1,142 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::game::{Event, Events, GameParts};
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "I/O error")]
+    Io(#[cause] io::Error),
+    #[fail(display = "Failed to write recording")]
+    Serialize(#[cause] ron::ser::Error),
+    #[fail(display = "Failed to read recording")]
+    Deserialize(#[cause] ron::de::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Self {
+        Error::Io(other)
+    }
+}
+
+//The state a battle started from plus every event that was applied to it, tick by tick
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Recording {
+    initial: GameParts,
+    ticks: Vec<Vec<Event>>,
+}
+impl Recording {
+    pub fn new(initial: GameParts) -> Recording {
+        Recording {
+            initial: initial,
+            ticks: Vec::new(),
+        }
+    }
+    pub fn record(&mut self, applied: &[Event]) {
+        self.ticks.push(applied.to_vec());
+    }
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+    //Forgets the last ticks, false if that would reach back past the initial state
+    pub fn rewind(&mut self, ticks: usize) -> bool {
+        if ticks > self.ticks.len() {
+            false
+        } else {
+            let len = self.ticks.len() - ticks;
+            self.ticks.truncate(len);
+            true
+        }
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let text = ron::ser::to_string(self).map_err(Error::Serialize)?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Recording, Error> {
+        let text = fs::read_to_string(path)?;
+        ron::de::from_str(&text).map_err(Error::Deserialize)
+    }
+}
+
+//Feeds a recording back through GameParts::push, the AI is never consulted so the
+//battle plays out exactly as it was recorded
+pub struct Replayer {
+    recording: Recording,
+    next: usize,
+}
+impl Replayer {
+    pub fn new(recording: Recording) -> Replayer {
+        Replayer {
+            recording: recording,
+            next: 0,
+        }
+    }
+    pub fn start(&mut self) -> GameParts {
+        self.next = 0;
+        self.recording.initial.clone()
+    }
+    pub fn step(&mut self, parts: &mut GameParts) -> Option<Events> {
+        let steps = self.recording.ticks.get(self.next)?;
+        self.next += 1;
+        Some(parts.replay_tick(steps))
+    }
+    pub fn ticks_played(&self) -> usize {
+        self.next
+    }
+    pub fn ticks_total(&self) -> usize {
+        self.recording.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Replayer;
+    use crate::game::entities::combat::CombatStance;
+    use crate::game::testing::{play, skirmish};
+    use crate::game::GameLoop;
+
+    #[test]
+    fn replay_reaches_the_recorded_state() {
+        let (mut game, events) = skirmish(3);
+        game.start_recording();
+        play(&mut game, events, 15);
+        assert!(game.get_battle_result().is_none());
+        let recording = game.stop_recording().unwrap();
+        assert_eq!(recording.len(), 15);
+
+        let mut replayer = Replayer::new(recording);
+        let mut parts = replayer.start();
+        while replayer.step(&mut parts).is_some() {}
+        assert_eq!(
+            ron::ser::to_string(&parts).unwrap(),
+            ron::ser::to_string(game.get_parts()).unwrap()
+        );
+    }
+
+    #[test]
+    fn replay_follows_the_turns() {
+        let (mut game, events) = skirmish(3);
+        let player = game.get_parts().get_entities().next().unwrap().0;
+        game.play_in_turns(vec![player]);
+        game.start_recording();
+        let mut events = events;
+        for _ in 0..30 {
+            if game.awaiting_input().is_some() && events.is_empty() {
+                game.take_turn(&mut events, CombatStance::FindingTarget)
+                    .unwrap();
+            }
+            events = GameLoop::execute_once(&mut game, events);
+        }
+        let recording = game.stop_recording().unwrap();
+
+        let mut replayer = Replayer::new(recording);
+        let mut parts = replayer.start();
+        while replayer.step(&mut parts).is_some() {}
+        assert_eq!(
+            ron::ser::to_string(&parts).unwrap(),
+            ron::ser::to_string(game.get_parts()).unwrap()
+        );
+    }
+}
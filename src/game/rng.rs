@@ -0,0 +1,115 @@
+use generational_arena::Index;
+
+//Every subsystem draws from its own stream so that adding a roll in one place
+//does not shift the outcome of rolls everywhere else
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RngStream {
+    Skills,
+    Combat,
+    Board,
+}
+impl RngStream {
+    fn salt(self) -> u64 {
+        match self {
+            RngStream::Skills => 0x5c11_5c11_5c11_5c11,
+            RngStream::Combat => 0xc0ba_7c0b_a7c0_ba7c,
+            RngStream::Board => 0xb0a2_db0a_2db0_a2d0,
+        }
+    }
+}
+
+//SplitMix64, small and with a fixed algorithm so a seed means the same battle on every build
+#[derive(Debug, Clone, Copy)]
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+    pub fn below(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            0
+        } else {
+            (self.next_u64() % n as u64) as u32
+        }
+    }
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        if high <= low {
+            low
+        } else {
+            low + self.below((high - low + 1) as u32) as i32
+        }
+    }
+    pub fn chance(&mut self, percent: i32) -> bool {
+        (self.below(100) as i32) < percent
+    }
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.below(items.len() as u32) as usize])
+        }
+    }
+}
+
+//Hands out generators keyed on (seed, stream, tick, entity) instead of sharing one
+//mutable generator, so the order entities are pumped in never changes what they roll
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RngService {
+    seed: u64,
+    tick: u64,
+}
+impl RngService {
+    pub fn new(seed: u64) -> RngService {
+        RngService {
+            seed: seed,
+            tick: 0,
+        }
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+    pub fn advance(&mut self) {
+        self.tick += 1;
+    }
+    pub fn stream(&self, stream: RngStream, id: Option<&Index>) -> Rng {
+        let (slot, generation) = match id {
+            Some(id) => id.into_raw_parts(),
+            None => (usize::max_value(), u64::max_value()),
+        };
+        let mut state = mix(self.seed ^ stream.salt());
+        state = mix(state ^ self.tick);
+        state = mix(state ^ slot as u64);
+        state = mix(state ^ generation);
+        Rng::new(state)
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::testing::{play, skirmish};
+
+    #[test]
+    fn same_seed_applies_the_same_events() {
+        let (mut first, events) = skirmish(7);
+        let (_, first_applied) = play(&mut first, events, 40);
+        let (mut second, events) = skirmish(7);
+        let (_, second_applied) = play(&mut second, events, 40);
+        assert!(!first_applied.is_empty());
+        assert_eq!(first_applied, second_applied);
+    }
+}
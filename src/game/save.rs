@@ -0,0 +1,95 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::game::{Events, GameLoop, GameParts};
+
+//Bump whenever a saved type changes shape, older saves are refused rather than misread
+pub const SAVE_VERSION: u32 = 18;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "I/O error")]
+    Io(#[cause] io::Error),
+    #[fail(display = "Failed to write save")]
+    Serialize(#[cause] ron::ser::Error),
+    #[fail(display = "Failed to read save")]
+    Deserialize(#[cause] ron::de::Error),
+    #[fail(
+        display = "Save was written by version {}, expected version {}",
+        found, expected
+    )]
+    UnsupportedVersion { found: u32, expected: u32 },
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Self {
+        Error::Io(other)
+    }
+}
+
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    parts: GameParts,
+    events: Events,
+}
+
+pub fn to_string(game: &GameLoop, events: &Events) -> Result<String, Error> {
+    ron::ser::to_string(&SaveFile {
+        version: SAVE_VERSION,
+        parts: game.get_parts().clone(),
+        events: events.clone(),
+    })
+    .map_err(Error::Serialize)
+}
+
+pub fn from_str(text: &str) -> Result<(GameLoop, Events), Error> {
+    let header: SaveHeader = ron::de::from_str(text).map_err(Error::Deserialize)?;
+    if header.version != SAVE_VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: header.version,
+            expected: SAVE_VERSION,
+        });
+    }
+    let save: SaveFile = ron::de::from_str(text).map_err(Error::Deserialize)?;
+    Ok((GameLoop::from_parts(save.parts), save.events))
+}
+
+pub fn save<P: AsRef<Path>>(path: P, game: &GameLoop, events: &Events) -> Result<(), Error> {
+    fs::write(path, to_string(game, events)?)?;
+    Ok(())
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(GameLoop, Events), Error> {
+    from_str(&fs::read_to_string(path)?)
+}
+
+pub fn slot_path<P: AsRef<Path>>(dir: P, slot: u32) -> PathBuf {
+    dir.as_ref().join(format!("slot_{}.ron", slot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_str, to_string};
+    use crate::game::testing::{play, skirmish};
+
+    #[test]
+    fn running_battle_round_trips() {
+        let (mut game, events) = skirmish(5);
+        let (events, _) = play(&mut game, events, 10);
+        let text = to_string(&game, &events).unwrap();
+        let (mut loaded, loaded_events) = from_str(&text).unwrap();
+        assert_eq!(to_string(&loaded, &loaded_events).unwrap(), text);
+
+        //Both carry on exactly alike
+        let (_, played) = play(&mut game, events, 20);
+        let (_, loaded_played) = play(&mut loaded, loaded_events, 20);
+        assert_eq!(played, loaded_played);
+    }
+}
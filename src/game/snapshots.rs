@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use crate::game::battle::outcome::BattleResult;
+use crate::game::{Events, GameParts};
+
+#[derive(Clone)]
+pub struct Snapshot {
+    tick: u64,
+    parts: GameParts,
+    events: Events,
+    //The result of the last battle if it had not been taken yet
+    battle_result: Option<BattleResult>,
+}
+impl Snapshot {
+    pub fn new(
+        parts: &GameParts,
+        events: &Events,
+        battle_result: &Option<BattleResult>,
+    ) -> Snapshot {
+        Snapshot {
+            tick: parts.get_tick(),
+            parts: parts.clone(),
+            events: events.clone(),
+            battle_result: battle_result.clone(),
+        }
+    }
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+    pub fn get_parts(&self) -> &GameParts {
+        &self.parts
+    }
+    pub fn get_events(&self) -> &Events {
+        &self.events
+    }
+    pub fn get_battle_result(&self) -> &Option<BattleResult> {
+        &self.battle_result
+    }
+    pub fn restore(self) -> (GameParts, Events, Option<BattleResult>) {
+        (self.parts, self.events, self.battle_result)
+    }
+}
+
+//Keeps the most recent snapshots, oldest first, dropping the oldest once full
+pub struct Snapshots {
+    capacity: usize,
+    ring: VecDeque<Snapshot>,
+}
+impl Snapshots {
+    pub fn new(capacity: usize) -> Snapshots {
+        Snapshots {
+            capacity: capacity,
+            ring: VecDeque::with_capacity(capacity),
+        }
+    }
+    pub fn take(
+        &mut self,
+        parts: &GameParts,
+        events: &Events,
+        battle_result: &Option<BattleResult>,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.ring.len() >= self.capacity {
+            self.ring.pop_front();
+        }
+        self.ring
+            .push_back(Snapshot::new(parts, events, battle_result));
+    }
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.ring.back()
+    }
+    pub fn at_or_before(&self, tick: u64) -> Option<&Snapshot> {
+        self.ring
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.tick <= tick)
+    }
+    pub fn clear(&mut self) {
+        self.ring.clear();
+    }
+
+    //Hands back the newest snapshot taken at or before the tick and forgets everything after it
+    //Nothing is forgotten when there is no such snapshot
+    pub fn rewind_to(&mut self, tick: u64) -> Option<Snapshot> {
+        let kept = self
+            .ring
+            .iter()
+            .rposition(|snapshot| snapshot.tick <= tick)?
+            + 1;
+        self.ring.truncate(kept);
+        self.ring.back().cloned()
+    }
+}
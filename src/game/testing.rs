@@ -0,0 +1,47 @@
+use crate::game::battle::system::board::{Board, ConstructionPart};
+use crate::game::entities::factions::FactionId;
+use crate::game::entities::traits::TraitId;
+use crate::game::entities::Change;
+use crate::game::{Event, EventBudget, Events, GameLoop};
+
+//Two Felines against two Rats on a small board, the battle already started
+pub fn skirmish(seed: u64) -> (GameLoop, Events) {
+    let mut game = GameLoop::with_seed(seed);
+    let mut events = Events::new();
+    let roster = vec![
+        (FactionId::Feline, (0, 0), Some(TraitId::Crusader)),
+        (FactionId::Feline, (1, 0), Some(TraitId::Mage)),
+        (FactionId::Rat, (5, 5), Some(TraitId::Ranger)),
+        (FactionId::Rat, (4, 5), None),
+    ];
+    let mut construction = Vec::new();
+    for (faction, pos, trt) in roster {
+        let id = game.add_entity(&mut events, None);
+        game.get_parts_mut().set_faction(&id, faction);
+        if let Some(trt) = trt {
+            events.push(1, Event::ChangeEntity(id, Change::AddTrait(trt)));
+        }
+        construction.push(ConstructionPart::new(pos, Some(id)));
+    }
+    game.start_battle(&mut events, Board::new(6, 6), construction)
+        .expect("the skirmish is laid out on the board");
+    (game, events)
+}
+
+//Plays the given number of ticks and hands back every event applied along the way
+pub fn play(game: &mut GameLoop, mut events: Events, ticks: usize) -> (Events, String) {
+    let mut applied = String::new();
+    for _ in 0..ticks {
+        events = GameLoop::execute_once(game, events);
+        applied.push_str(&ron::ser::to_string(game.get_parts().get_applied()).unwrap());
+    }
+    (events, applied)
+}
+
+//Applies the events and everything they lead to, without anyone getting a turn to act
+pub fn settle(game: &mut GameLoop, mut events: Events) {
+    let budget = EventBudget::unlimited();
+    while (1..=5).any(|pri| events.pending(pri) > 0) {
+        events = events.step(game.get_parts_mut(), &budget);
+    }
+}
@@ -0,0 +1,16 @@
+use crate::game::entities::VisualId;
+use crate::gui::animation::img::Img;
+
+pub struct Gallery {
+    imgs: Vec<Img>,
+}
+
+impl Gallery {
+    pub fn new() -> Gallery {
+        Gallery { imgs: Vec::new() }
+    }
+    pub fn add(&mut self, img: Img) -> VisualId {
+        self.imgs.push(img);
+        VisualId(self.imgs.len() - 1)
+    }
+}
@@ -12,6 +12,7 @@ struct Vertex {
 }
 
 
+pub mod gallery;
 pub mod img;
 pub mod texture;
 pub mod animaton;
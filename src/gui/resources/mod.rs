@@ -44,6 +44,13 @@ impl Resources {
         Ok(unsafe { ffi::CString::from_vec_unchecked(buffer) })
     }
 
+    pub fn load_string<P: AsRef<ResourcePath>>(&self, rel_path: P) -> Result<String, Error> {
+        Ok(fs::read_to_string(resource_name_to_path(
+            &self.root_path,
+            rel_path.as_ref(),
+        ))?)
+    }
+
     pub fn load_rgb_image<P: AsRef<ResourcePath>>(
         &self,
         rel_path: P,
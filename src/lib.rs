@@ -0,0 +1,14 @@
+extern crate gl;
+extern crate vec_2_10_10_10;
+#[macro_use]
+extern crate failure;
+#[macro_use]
+extern crate auto_claw_render_gl_derive as render_gl_derive;
+extern crate rayon;
+extern crate ron;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+
+pub mod game;
+pub mod gui;
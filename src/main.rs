@@ -1,19 +1,18 @@
+extern crate auto_claw;
+extern crate failure;
 extern crate gl;
 extern crate sdl2;
-extern crate vec_2_10_10_10;
-#[macro_use]
-extern crate failure;
-#[macro_use]
-extern crate auto_claw_render_gl_derive as render_gl_derive;
-extern crate rayon;
 
 mod debug;
 
 use failure::err_msg;
 
-pub mod game;
-pub mod gui;
+use auto_claw::game::definitions::Definitions;
+use auto_claw::game::{Events, GameLoop};
+use auto_claw::gui;
 
+use gui::animation::gallery::Gallery;
+use gui::animation::img::Img;
 use gui::render_gl::Viewport;
 use gui::resources::Resources;
 
@@ -50,10 +49,14 @@ fn run() -> Result<(), failure::Error> {
         gl.ClearColor(0.3, 0.3, 0.5, 1.0);
     }
 
-    let mut game = crate::game::GameLoop::new();
-    let mut events = crate::game::Events::new();
+    let mut game = GameLoop::new();
+    game.get_parts_mut()
+        .set_definitions(Definitions::from_res(&res)?);
+    let mut events = Events::new();
 
-    game.add_entity(&mut events, &res, &gl)?;
+    let mut gallery = Gallery::new();
+    let visual = gallery.add(Img::new(&res, &gl)?);
+    game.add_entity(&mut events, Some(visual));
 
     // main loop
 
@@ -90,7 +93,7 @@ fn run() -> Result<(), failure::Error> {
         tick += (current_millis - previous_millis) * 100000 / millis_per_frame;
         let ticks = tick / 100000;
 
-        events = crate::game::GameLoop::execute(&mut game, &ticks, events);
+        events = GameLoop::execute(&mut game, &ticks, events);
         //e.render(&gl,&ticks);
         window.gl_swap_window();
 